@@ -0,0 +1,95 @@
+//! Throughput of the physics step and of drawing, on a few typical scenes.
+//! Run with `cargo bench`, adding `--features parallel` to also time the
+//! threaded sweep at a few thread counts.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use sand_game::physics::step;
+use sand_game::render::Pixels;
+use sand_game::sand::{Game, ParticleKind};
+
+const SIZE: usize = 256;
+const WARMUP_TICKS: usize = 20;
+/// Ticks timed per run of a scene. Scenes settle as they run, so each run
+/// starts from a fresh scene to keep the timings comparable.
+const TICKS: usize = 50;
+const MIN_DURATION: Duration = Duration::from_secs(2);
+
+fn scene(fill: impl Fn(usize, usize) -> Option<&'static str>) -> Game {
+    let mut game = Game::new(SIZE, SIZE);
+    game.particle_system.reseed(0);
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            if let Some(name) = fill(x, y) {
+                let kind: ParticleKind = game.particle_system.elements.find(name)
+                    .expect("Missing base element");
+                game.particle_system.create_particle(x, y, kind)
+                    .expect("Cell outside the scene");
+            }
+        }
+    }
+    game
+}
+
+/// Average time per call of `f`, repeated for at least `MIN_DURATION`.
+fn time(mut f: impl FnMut()) -> Duration {
+    let start = Instant::now();
+    let mut n = 0;
+    while start.elapsed() < MIN_DURATION {
+        f();
+        n += 1;
+    }
+    start.elapsed() / n
+}
+
+fn bench_step(name: &str, scene: impl Fn() -> Game) {
+    let mut elapsed = Duration::ZERO;
+    let mut n = 0;
+    while elapsed < MIN_DURATION {
+        let mut game = scene();
+        for _ in 0..WARMUP_TICKS {
+            step(&mut game.particle_system).expect("Step failed");
+        }
+        let start = Instant::now();
+        for _ in 0..TICKS {
+            step(black_box(&mut game.particle_system)).expect("Step failed");
+        }
+        elapsed += start.elapsed();
+        n += TICKS as u32;
+    }
+    println!("step/{:<12} {:>10.3?}", name, elapsed / n);
+}
+
+fn bench_draw(name: &str, game: Game) {
+    let mut pixels = Pixels::new(SIZE, SIZE);
+    let per_frame = time(|| game.draw(black_box(&mut pixels)));
+    println!("draw/{:<12} {:>10.3?}", name, per_frame);
+}
+
+fn main() {
+    let falling = || scene(|x, y| (y > SIZE / 2 && (x + y) % 2 == 0).then_some("Sand"));
+    let settled = || scene(|_, y| (y < SIZE / 3).then_some("Sand"));
+    let pool = || scene(|x, y| (y < SIZE / 2 && x > SIZE / 4).then_some("Water"));
+    let mixed = || scene(|x, y| match (x * 4 / SIZE, y < SIZE / 2) {
+        (0, true) => Some("Sand"),
+        (1, true) => Some("Water"),
+        (2, true) => Some("Wood"),
+        (3, true) => Some("Lava"),
+        _ => None,
+    });
+    bench_step("falling", falling);
+    bench_step("settled", settled);
+    bench_step("pool", pool);
+    bench_step("mixed", mixed);
+    bench_draw("mixed", mixed());
+    // How the threaded sweep scales; the default above uses every core
+    #[cfg(feature = "parallel")]
+    for threads in [1, 2, 4] {
+        bench_step(&format!("falling/{}t", threads), || {
+            let mut game = falling();
+            game.particle_system.set_sweep_threads(threads);
+            game
+        });
+    }
+}
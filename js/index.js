@@ -1,6 +1,35 @@
-import init, { WasmGameContext } from 'rust-wasm-sand';
+import init, { WasmGameContext, WasmRenderer } from 'rust-wasm-sand';
 
 const RESCALE = 4;
+// Run the simulation in a Web Worker, leaving the main thread to upload frames
+// and handle input, so heavy scenes do not freeze the page
+const USE_WORKER = typeof Worker !== "undefined";
+const MIN_TIME_SCALE = 0.25;
+const MAX_TIME_SCALE = 8;
+const EMITTER_RATE = 0.2;
+const FORCE_STRENGTH = 0.3;
+const WIND_KEYS = {
+    "ArrowLeft": [-FORCE_STRENGTH, 0],
+    "ArrowRight": [FORCE_STRENGTH, 0],
+    "ArrowUp": [0, FORCE_STRENGTH],
+    "ArrowDown": [0, -FORCE_STRENGTH],
+};
+const BRUSH_KEYS = {
+    "1": "Sand",
+    "2": "Water",
+    "3": "Oil",
+    "4": "Wall",
+    "5": "Stone",
+    "6": "Steam",
+    "7": "Smoke",
+    "8": "Lava",
+    "9": "Ice",
+    "f": "Fire",
+    "w": "Wood",
+    "g": "Gunpowder",
+    "t": "TNT",
+    "e": "Ember",
+};
 
 (async () => {
     await init();
@@ -12,37 +41,136 @@ const RESCALE = 4;
     }
     const [gameWidth, gameHeight] = [width/RESCALE, height/RESCALE];
 
+    const callGame = USE_WORKER
+        ? startWorker(canvas, [gameWidth, gameHeight])
+        : startMainThread(canvas, [gameWidth, gameHeight]);
+    setMouseHooks(canvas, [gameWidth, gameHeight], callGame);
+    setKeyHooks(callGame);
+})()
+
+// Both start functions return a function calling a method on the game context
+// by name, wherever the game runs.
+
+function startMainThread(canvas, dims) {
+    const [gameWidth, gameHeight] = dims;
     const gameContext = WasmGameContext.new(gameWidth, gameHeight);
     gameContext.bind_canvas(canvas);
-    setMouseHooks(canvas, [gameWidth, gameHeight], gameContext);
+    gameContext.set_running(true, performance.now());
 
+    // Errors from the game are logged rather than thrown, so a failing tick
+    // does not stop the render loop
+    const callGame = (method, ...args) => {
+        try {
+            return gameContext[method](...args);
+        }
+        catch (err) {
+            console.error(err);
+        }
+    };
     const render = (timestamp) => {
-        gameContext.render();
-        gameContext.update(timestamp);
+        callGame("render");
+        callGame("update", timestamp);
         window.requestAnimationFrame(render);
     }
 
     window.requestAnimationFrame(render);
-})()
+    return callGame;
+}
+
+function startWorker(canvas, dims) {
+    const [gameWidth, gameHeight] = dims;
+    const renderer = WasmRenderer.new(canvas);
+    const worker = new Worker(new URL("./worker.js", import.meta.url), { type: "module" });
+    let frame = null;
+    worker.onmessage = (event) => {
+        if (event.data.type === "frame") {
+            frame = event.data.pixels;
+        }
+    };
+    worker.postMessage({ type: "init", width: gameWidth, height: gameHeight });
+
+    // Show the newest frame and ask for the next one, so the worker never
+    // draws faster than the page can display
+    const render = () => {
+        if (frame !== null) {
+            renderer.render(frame);
+            frame = null;
+            worker.postMessage({ type: "ack" });
+        }
+        window.requestAnimationFrame(render);
+    }
+
+    window.requestAnimationFrame(render);
+    return (method, ...args) => worker.postMessage({ type: "call", method, args });
+}
+
+function setKeyHooks(callGame) {
+    let lastElement = "Sand";
+    // Tracked here since the game may live in a worker we cannot query
+    let paused = false;
+    let timeScale = 1;
+    document.addEventListener("keydown", (event) => {
+        if (event.key === " ") {
+            paused = !paused;
+            callGame(paused ? "pause" : "resume");
+        }
+        else if (event.key === ".") {
+            callGame("step_once", 1);
+        }
+        else if (event.key === "+" || event.key === "=") {
+            timeScale = Math.min(timeScale * 2, MAX_TIME_SCALE);
+            callGame("set_time_scale", timeScale);
+        }
+        else if (event.key === "-") {
+            timeScale = Math.max(timeScale / 2, MIN_TIME_SCALE);
+            callGame("set_time_scale", timeScale);
+        }
+        else if (event.key === "0") {
+            callGame("set_brush_eraser");
+        }
+        else if (event.key === "d") {
+            callGame("set_brush_drain");
+        }
+        else if (event.key === "s") {
+            callGame("set_brush_emitter", lastElement, EMITTER_RATE);
+        }
+        else if (event.key === "a") {
+            callGame("set_brush_attractor", FORCE_STRENGTH);
+        }
+        else if (event.key === "r") {
+            callGame("set_brush_attractor", -FORCE_STRENGTH);
+        }
+        else if (event.key === "v") {
+            callGame("set_brush_vortex", FORCE_STRENGTH);
+        }
+        else if (event.key in WIND_KEYS) {
+            callGame("set_brush_wind", ...WIND_KEYS[event.key]);
+        }
+        else if (event.key in BRUSH_KEYS) {
+            lastElement = BRUSH_KEYS[event.key];
+            callGame("set_brush_element", lastElement);
+        }
+    });
+}
 
-function setMouseHooks(canvas, dims, gameContext) {
+function setMouseHooks(canvas, dims, callGame) {
     const [_, gameHeight] = dims;
     canvas.addEventListener("mousedown", (event) => {
         console.log("mouse down");
-        gameContext.mouse_down(
+        callGame("mouse_down",
             event.offsetX / RESCALE,
             gameHeight - event.offsetY / RESCALE);
     });
     canvas.addEventListener("mouseup", (event) => {
         console.log("mouse up");
-        gameContext.mouse_up(
+        callGame("mouse_up",
             event.offsetX / RESCALE,
             gameHeight - event.offsetY / RESCALE);
     });
     canvas.addEventListener("mousemove", (event) => {
         console.log("mouse move");
-        gameContext.mouse_move(
+        callGame("mouse_move",
             event.offsetX / RESCALE,
             gameHeight - event.offsetY / RESCALE);
     });
-}
\ No newline at end of file
+}
@@ -0,0 +1,58 @@
+import init, { WasmGameContext } from 'rust-wasm-sand';
+
+// Runs the game off the main thread. The page sends input as method calls on
+// the game context and gets drawn frames back, one per acknowledgement.
+const TICK_INTERVAL_MS = 1000 / 60;
+
+let gameContext = null;
+let frameWanted = true;
+// Calls arriving before the wasm module has loaded
+const pendingCalls = [];
+
+self.onmessage = (event) => {
+    const message = event.data;
+    if (message.type === "init") {
+        start(message.width, message.height);
+    }
+    else if (message.type === "call") {
+        if (gameContext === null) {
+            pendingCalls.push(message);
+        }
+        else {
+            call(message.method, message.args);
+        }
+    }
+    else if (message.type === "ack") {
+        frameWanted = true;
+    }
+};
+
+async function start(width, height) {
+    await init();
+    gameContext = WasmGameContext.new(width, height);
+    gameContext.set_running(true, performance.now());
+    for (const { method, args } of pendingCalls) {
+        call(method, args);
+    }
+    setInterval(tick, TICK_INTERVAL_MS);
+}
+
+// Errors from the game are logged rather than thrown, so one bad call or tick
+// does not stop the worker
+function call(method, args) {
+    try {
+        gameContext[method](...args);
+    }
+    catch (err) {
+        console.error(err);
+    }
+}
+
+function tick() {
+    call("update", [performance.now()]);
+    if (frameWanted) {
+        frameWanted = false;
+        const pixels = gameContext.draw_pixels();
+        self.postMessage({ type: "frame", pixels }, [pixels.buffer]);
+    }
+}
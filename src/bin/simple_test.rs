@@ -1,13 +1,15 @@
+use sand_game::error::SandError;
 use sand_game::physics::step;
-use sand_game::sand::{Game, Particle, ParticleKind};
+use sand_game::sand::{Game, ParticleKind};
 
-pub fn main() {
+pub fn main() -> Result<(), SandError> {
     let mut game = Game::new(128, 128);
-    game.particle_system.particles.insert(Particle {
-        kind: ParticleKind::Base(0),
-        position: (0, 0),
-        velocity: (0.0, 0.0),
-    });
+    game.particle_system.create_particle(0, 0, ParticleKind::Base(0))?;
     game.running = true;
-    step(&mut game.particle_system);
+    step(&mut game.particle_system)?;
+    let violations = game.particle_system.validate();
+    if !violations.is_empty() {
+        println!("Violations: {:?}", violations);
+    }
+    Ok(())
 }
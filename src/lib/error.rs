@@ -0,0 +1,47 @@
+use std::fmt;
+
+use crate::sand::{ParticleId, ParticleKind, Violation};
+
+/// Everything that can go wrong when driving the simulation.
+#[derive(Clone,Debug,PartialEq)]
+pub enum SandError {
+    /// A cell outside the grid was addressed.
+    OutOfBounds { x: usize, y: usize },
+    /// An operation needed a particle in a cell that was empty.
+    MissingParticle { x: usize, y: usize },
+    /// No element has the given name.
+    UnknownElement(String),
+    /// A kind with no entry in the element table was used.
+    UnknownKind(ParticleKind),
+    /// A particle id that was never handed out, or the empty id.
+    InvalidId(ParticleId),
+    /// No boundary mode has the given name.
+    UnknownBoundaryMode(String),
+    /// No edge of the world has the given name.
+    UnknownEdge(String),
+    /// A frame of pixel data does not match the size of its target.
+    FrameSize { expected: usize, len: usize },
+    /// The particle storage broke its invariants; see
+    /// `ParticleSystem::validate`.
+    Inconsistent(Vec<Violation>),
+}
+
+impl fmt::Display for SandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SandError::OutOfBounds { x, y } => write!(f, "Cell {},{} is out of bounds", x, y),
+            SandError::MissingParticle { x, y } => write!(f, "No particle at {},{}", x, y),
+            SandError::UnknownElement(name) => write!(f, "Unknown element {}", name),
+            SandError::UnknownKind(kind) => write!(f, "Unknown particle kind {:?}", kind),
+            SandError::InvalidId(id) => write!(f, "Invalid particle id {}", id.0),
+            SandError::UnknownBoundaryMode(mode) => write!(f, "Unknown boundary mode {}", mode),
+            SandError::UnknownEdge(edge) => write!(f, "Unknown edge {}", edge),
+            SandError::FrameSize { expected, len } =>
+                write!(f, "Pixel data has {} bytes, expected {}", len, expected),
+            SandError::Inconsistent(violations) =>
+                write!(f, "Inconsistent particle system: {:?}", violations),
+        }
+    }
+}
+
+impl std::error::Error for SandError {}
@@ -1,71 +1,181 @@
-use crate::{sand::{Game, ParticleSystem, Brush, Particle, BrushKind, ParticleKind, ParticleInd}, util::Coord};
+use crate::{error::SandError, sand::{Game, ParticleSystem, Brush, Particle, BrushKind, ParticleKind, Fixture}, util::Coord};
 use core::cmp::{max, min};
+use rand::Rng;
 
 pub enum MouseState {
     Up,
     Down(Coord),
 }
 
-pub fn handle_mouse_click(coord: Coord, game: &mut Game) {
-    game.particle_system.fill_circle(coord, &game.brush);
+pub fn handle_mouse_click(coord: Coord, game: &mut Game) -> Result<(), SandError> {
+    game.particle_system.fill_circle(coord, &game.brush)
 }
 
-pub fn handle_mouse_drag(start: Coord, end: Coord, game: &mut Game) {
+pub fn handle_mouse_drag(start: Coord, end: Coord, game: &mut Game) -> Result<(), SandError> {
     game.particle_system.fill_line(start, end, &game.brush)
 }
 
 
 impl ParticleSystem {
-    fn delete_particle(&mut self, i: ParticleInd) {
-        let particle: &Particle = self.particles.get(i).expect("Missing particle");
-        self.grid.set(particle.position.0, particle.position.1, None);
-        self.particles.remove(i);
+    /// Write a particle into a cell, or empty it with `None`. The particle's
+    /// kind must be in the element table and its id one from `alloc_id`.
+    pub fn set_particle(&mut self, x: usize, y: usize, particle: Option<Particle>) -> Result<(), SandError> {
+        if let Some(particle) = &particle {
+            self.elements.check(particle.kind)?;
+            if !self.is_allocated(particle.id) {
+                return Err(SandError::InvalidId(particle.id));
+            }
+        }
+        self.grid.set(x, y, particle)?;
+        self.wake(x, y);
+        Ok(())
+    }
+
+    pub fn delete_particle(&mut self, x: usize, y: usize) -> Result<(), SandError> {
+        self.particle_at(x, y)?;
+        self.grid.set(x, y, None)?;
+        self.wake(x, y);
+        Ok(())
     }
 
-    fn create_particle(&mut self, x: usize, y: usize, kind: ParticleKind) {
-        let i = self.particles.insert(Particle {
-            kind: kind,
-            position: (x, y),
+    /// Place a new particle, replacing whatever occupied the cell.
+    pub fn create_particle(&mut self, x: usize, y: usize, kind: ParticleKind) -> Result<(), SandError> {
+        self.elements.check(kind)?;
+        if x >= self.grid.width || y >= self.grid.height {
+            return Err(SandError::OutOfBounds { x, y });
+        }
+        let lifetime = self.initial_lifetime(kind);
+        let temperature = self.initial_temperature(kind);
+        let id = self.alloc_id();
+        self.grid.set(x, y, Some(Particle {
+            kind,
             velocity: (0.0, 0.0),
-        });
-        self.grid.set(x, y, Some(i));
+            lifetime,
+            temperature,
+            burning: None,
+            id,
+        }))?;
+        self.wake(x, y);
+        Ok(())
+    }
+
+    /// Turn the particle in a cell into another kind in place, keeping its id,
+    /// velocity and temperature. Any fire is put out.
+    pub fn convert_particle(&mut self, x: usize, y: usize, kind: ParticleKind) -> Result<(), SandError> {
+        self.elements.check(kind)?;
+        self.particle_at(x, y)?;
+        let lifetime = self.initial_lifetime(kind);
+        let i = self.grid.ind(x, y);
+        self.grid.kinds[i] = Some(kind);
+        self.grid.lifetimes[i] = lifetime;
+        self.grid.burning[i] = None;
+        self.wake(x, y);
+        Ok(())
+    }
+
+    /// Kind of the particle in the cell, failing if the cell is empty or
+    /// outside the grid.
+    pub fn particle_at(&self, x: usize, y: usize) -> Result<ParticleKind, SandError> {
+        if x >= self.grid.width || y >= self.grid.height {
+            return Err(SandError::OutOfBounds { x, y });
+        }
+        self.grid.kind(x, y).ok_or(SandError::MissingParticle { x, y })
+    }
+
+    /// Temperature of a newly made particle of the given kind: its element's
+    /// own, or else that of the surrounding air.
+    pub(crate) fn initial_temperature(&self, kind: ParticleKind) -> f64 {
+        self.elements.get(kind).temperature.unwrap_or(self.ambient_temperature)
+    }
+
+    fn initial_lifetime(&mut self, kind: ParticleKind) -> Option<u32> {
+        self.elements.get(kind).lifetime
+            .map(|(lo, hi)| self.rng.gen_range(lo..=hi))
     }
 
-    pub fn draw_point(&mut self, x: usize, y: usize, brush_kind: &BrushKind) {
-        let cur_val = self.grid.get(x, y);
+    /// Apply the brush to a single cell. Force brushes treat the cell as the
+    /// centre of their zone; strokes shape them with `paint_force` instead.
+    pub fn draw_point(&mut self, x: usize, y: usize, brush_kind: &BrushKind) -> Result<(), SandError> {
+        if x >= self.grid.width || y >= self.grid.height {
+            return Err(SandError::OutOfBounds { x, y });
+        }
+        let cur_val = self.grid.kind(x, y);
         match brush_kind {
             BrushKind::Eraser => {
-                if let Some(i) = cur_val {
-                    self.delete_particle(i);
+                if cur_val.is_some() {
+                    self.delete_particle(x, y)?;
                 }
+                self.fixtures.remove(&(x, y));
+                self.forces.set(x, y, (0.0, 0.0))?;
+                self.wake(x, y);
             }
             BrushKind::Draw(kind) => {
-                if let None = cur_val {
-                    self.create_particle(x, y, *kind);
+                if cur_val.is_none() && !self.is_drain(x, y) {
+                    self.create_particle(x, y, *kind)?;
                 }
             }
+            BrushKind::Emitter(kind, rate) => {
+                self.elements.check(*kind)?;
+                self.fixtures.insert((x, y), Fixture::Emitter { kind: *kind, rate: *rate });
+            }
+            BrushKind::Drain => {
+                self.fixtures.insert((x, y), Fixture::Drain);
+            }
+            BrushKind::Force(force) => {
+                self.paint_force(x, y, force.accel((0.0, 0.0)))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Set the acceleration of a cell's force zone.
+    pub fn paint_force(&mut self, x: usize, y: usize, accel: (f64, f64)) -> Result<(), SandError> {
+        self.forces.set(x, y, accel)?;
+        self.wake(x, y);
+        Ok(())
+    }
+
+    /// Apply a brush stroke to one of its cells. Force zones are shaped by
+    /// the cell's offset from `center`, the nearest point on the stroke.
+    fn draw_stroke_point(&mut self, x: usize, y: usize, center: Coord, brush_kind: &BrushKind) -> Result<(), SandError> {
+        match brush_kind {
+            BrushKind::Force(force) => {
+                let offset = (x as f64 - center.x, y as f64 - center.y);
+                self.paint_force(x, y, force.accel(offset))
+            }
+            _ => self.draw_point(x, y, brush_kind),
         }
     }
 
-    pub fn fill_circle(&mut self, coord: Coord, brush: &Brush) {
-        let x0 = (coord.x - brush.radius).floor().clamp(0.0, f64::INFINITY) as usize;
-        let y0 = (coord.y - brush.radius).floor().clamp(0.0, f64::INFINITY) as usize;
-        let x1 = (coord.x + brush.radius).ceil().clamp(0.0, f64::INFINITY) as usize;
-        let y1 = (coord.y + brush.radius).ceil().clamp(0.0, f64::INFINITY) as usize;
+    /// Grid cells within `radius` of `coord`, clipped to the grid.
+    pub fn circle_points(&self, coord: Coord, radius: f64) -> Vec<(usize, usize)> {
+        let x0 = (coord.x - radius).floor().clamp(0.0, f64::INFINITY) as usize;
+        let y0 = (coord.y - radius).floor().clamp(0.0, f64::INFINITY) as usize;
+        let x1 = ((coord.x + radius).ceil().clamp(0.0, f64::INFINITY) as usize).min(self.grid.width);
+        let y1 = ((coord.y + radius).ceil().clamp(0.0, f64::INFINITY) as usize).min(self.grid.height);
+        let mut points = vec![];
         for x in x0..x1 {
             let dx = x as f64 - coord.x; // TODO: offset by half pixel?
             for y in y0..y1 {
                 let dy = y as f64 - coord.y;
-                if dx*dx + dy*dy <= brush.radius*brush.radius {
-                    self.draw_point(x, y, &brush.kind);
+                if dx*dx + dy*dy <= radius*radius {
+                    points.push((x, y));
                 }
             }
         }
+        points
+    }
+
+    pub fn fill_circle(&mut self, coord: Coord, brush: &Brush) -> Result<(), SandError> {
+        for (x, y) in self.circle_points(coord, brush.radius) {
+            self.draw_stroke_point(x, y, coord, &brush.kind)?;
+        }
+        Ok(())
     }
 
-    pub fn fill_line(&mut self, start: Coord, end: Coord, brush: &Brush) {
-        self.fill_circle(start, brush);
-        self.fill_circle(end, brush);
+    pub fn fill_line(&mut self, start: Coord, end: Coord, brush: &Brush) -> Result<(), SandError> {
+        self.fill_circle(start, brush)?;
+        self.fill_circle(end, brush)?;
 
         let lx = end.x - start.x;
         let ly = end.y - start.y;
@@ -86,10 +196,13 @@ impl ParticleSystem {
                 // change basis to line coords
                 let v_tangent = (lx * vx + ly * vy) / (norm*norm);
                 let v_perp = (-ly * vx + lx * vy) / (norm*brush.radius);
-                if v_tangent >= 0.0 && v_tangent <= 1.0 && v_perp >= -1.0 && v_perp <= 1.0 {
-                    self.draw_point(x, y, &brush.kind);
+                if (0.0..=1.0).contains(&v_tangent) && (-1.0..=1.0).contains(&v_perp) {
+                    // Centre on the nearest point of the stroke's spine
+                    let center = Coord::new(start.x + v_tangent * lx, start.y + v_tangent * ly);
+                    self.draw_stroke_point(x, y, center, &brush.kind)?;
                 }
             }
         }
+        Ok(())
     }
 }
\ No newline at end of file
@@ -1,3 +1,4 @@
+pub mod error;
 pub mod input;
 pub mod physics;
 pub mod render;
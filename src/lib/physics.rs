@@ -1,7 +1,1080 @@
-use crate::sand::{ParticleSystem};
+use std::collections::{BTreeMap, VecDeque};
+use std::f64::consts::FRAC_PI_4;
+use std::marker::PhantomData;
+use std::ops::Range;
+use rand::{Rng, SeedableRng};
+use rand::seq::SliceRandom;
+use rand_chacha::ChaCha8Rng;
 
-pub fn step(system: &mut ParticleSystem) {
-    // for particle in system.particles.iter() {
-    //     println!("{:?}", particle);
-    // }
+use crate::sand::{
+    ParticleSystem, Grid, ParticleKind, ParticleId, ElementState, Elements, Target,
+    Fixture, Boundaries, ForceField, CHUNK_SIZE, DEFAULT_AMBIENT_TEMPERATURE};
+#[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+use std::{panic::{self, AssertUnwindSafe}, sync::mpsc, thread::{self, JoinHandle}};
+#[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+use crate::sand::BoundaryMode;
+use crate::error::SandError;
+use crate::util::Coord;
+
+/// Speed cap in cells/tick, bounding how far a particle is traced per tick.
+const MAX_SPEED: f64 = 8.0;
+/// Cap on how far a liquid spreads sideways per tick, whatever its dispersion.
+const MAX_SPREAD: usize = 8;
+// Chunks swept at the same time are a chunk apart, so nothing may reach past
+// half a chunk, counting the neighbours looked at around the destination
+const _: () = assert!(MAX_SPEED as usize + 2 <= CHUNK_SIZE / 2 && MAX_SPREAD + 2 <= CHUNK_SIZE / 2);
+/// Fraction of velocity handed to the particle that was struck.
+const IMPACT_TRANSFER: f64 = 0.3;
+/// Fraction of vertical speed converted to sideways speed on landing.
+const SPLASH: f64 = 0.4;
+/// Fraction of sideways speed reversed when hitting an obstacle side-on.
+const RESTITUTION: f64 = 0.2;
+/// Fraction of velocity kept after pushing through a lighter particle.
+const DISPLACE_DRAG: f64 = 0.5;
+/// Fraction of sideways speed kept per tick while resting on something.
+const GROUND_FRICTION: f64 = 0.7;
+/// Fraction of velocity gases keep per tick, limiting how fast they drift.
+const GAS_DRAG: f64 = 0.8;
+/// Rate at which heat flows between neighbours, scaled by conductivity. Kept
+/// below 1/4 so a cell can never overshoot its four neighbours' temperatures.
+const HEAT_DIFFUSION: f64 = 0.2;
+/// Conductivity of the ambient air filling empty cells.
+const AIR_CONDUCTIVITY: f64 = 0.02;
+/// Temperature change per tick below which a particle lets its chunk sleep.
+const SETTLED_HEAT: f64 = 0.01;
+/// Speed in cells/tick below which an unsupported particle lets its chunk
+/// sleep.
+const SETTLED_SPEED: f64 = 0.01;
+/// Offsets to the four edge-adjacent neighbours.
+const CARDINAL_OFFSETS: [(i64, i64); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+/// Temperature burning particles are held at, so they heat their surroundings.
+const FLAME_TEMPERATURE: f64 = 800.0;
+/// Chance per tick that a burning particle releases a flame into an empty
+/// neighbour.
+const FLAME_EMISSION: f64 = 0.3;
+/// Chance per tick that a burning particle releases smoke into an empty
+/// neighbour.
+const SMOKE_EMISSION: f64 = 0.05;
+/// Speed in cells/tick given to a surviving particle per unit of blast force.
+const BLAST_IMPULSE: f64 = 0.5;
+/// Chance per unit of blast falloff that an empty cell in the blast fills
+/// with a flame.
+const FIREBALL: f64 = 0.5;
+/// Offsets to all eight neighbours.
+const NEIGHBOUR_OFFSETS: [(i64, i64); 8] = [
+    (-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)];
+/// The eight neighbour offsets in counter-clockwise order, so rotating a
+/// direction by 45 degrees is one step along the ring.
+const DIRECTIONS: [(i64, i64); 8] = [
+    (1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1), (0, -1), (1, -1)];
+/// Half of the eight neighbour offsets; scanning these from every cell visits
+/// each adjacent pair exactly once.
+const REACTION_OFFSETS: [(i64, i64); 4] = [(1, 0), (-1, 1), (0, 1), (1, 1)];
+
+/// Advance the particle system by one tick.
+///
+/// Only particles in awake chunks are simulated; see `Grid`. Everything that
+/// changes a particle wakes its surroundings for the next tick, so settled
+/// regions fall asleep and cost nothing until disturbed.
+///
+/// Fails if the system refers to something that does not exist, e.g. a
+/// fixture outside the grid or a reaction producing an unknown kind. Debug
+/// builds also check `ParticleSystem::validate` after every tick and fail with
+/// `SandError::Inconsistent` on any violation.
+pub fn step(system: &mut ParticleSystem) -> Result<(), SandError> {
+    system.grid.advance_chunks();
+    run_fixtures(system)?;
+    react(system)?;
+    conduct_heat(system);
+    transition_phases(system)?;
+    combust(system)?;
+    decay(system)?;
+    move_particles(system);
+    #[cfg(debug_assertions)]
+    {
+        let violations = system.validate();
+        if !violations.is_empty() {
+            return Err(SandError::Inconsistent(violations));
+        }
+    }
+    Ok(())
+}
+
+/// Drain any particle sitting on a drain, e.g. one drawn before the drain was
+/// placed, and let emitters spawn into their cells if empty.
+fn run_fixtures(system: &mut ParticleSystem) -> Result<(), SandError> {
+    let fixtures: Vec<((usize, usize), Fixture)> = system.fixtures.iter()
+        .map(|(&pos, &fixture)| (pos, fixture))
+        .collect();
+    for ((x, y), fixture) in fixtures {
+        let occupant = system.grid.kind(x, y);
+        match fixture {
+            Fixture::Drain => {
+                if occupant.is_some() {
+                    system.delete_particle(x, y)?;
+                }
+            }
+            Fixture::Emitter { kind, rate } => {
+                if occupant.is_none() && system.rng.gen::<f64>() < rate {
+                    system.create_particle(x, y, kind)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Let adjacent particles react according to the element reaction table.
+/// A particle takes part in at most one reaction per tick. Pairs that could
+/// react but did not keep their chunk awake, so sleeping never stops them.
+fn react(system: &mut ParticleSystem) -> Result<(), SandError> {
+    let width = system.grid.width;
+    let mut reacted = vec![false; width * system.grid.height];
+    for y in 0..system.grid.height {
+        for x in system.grid.awake_spans(y).into_iter().flatten() {
+            if reacted[y * width + x] {
+                continue;
+            }
+            let Some(a) = system.grid.kind(x, y) else {
+                continue;
+            };
+            for offset in REACTION_OFFSETS {
+                let Target::Cell(nx, ny) = system.resolve_offset((x, y), offset) else {
+                    continue;
+                };
+                if reacted[ny * width + nx] || (nx, ny) == (x, y) {
+                    continue;
+                }
+                let Some(b) = system.grid.kind(nx, ny) else {
+                    continue;
+                };
+                let Some(&reaction) = system.elements.reaction(a, b) else {
+                    continue;
+                };
+                if system.rng.gen::<f64>() >= reaction.probability {
+                    // Keep rolling next tick, as if the chunk never slept
+                    system.wake(x, y);
+                    continue;
+                }
+                apply_product(system, (x, y), reaction.products.0)?;
+                apply_product(system, (nx, ny), reaction.products.1)?;
+                reacted[y * width + x] = true;
+                reacted[ny * width + nx] = true;
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Replace the particle at `pos` by the product of a reaction, burning or
+/// decay, or remove it if there is none. Unlike a phase transition, the
+/// product starts at its element's own temperature rather than keeping the
+/// old one, which could push it straight back over a transition threshold.
+fn apply_product(
+        system: &mut ParticleSystem, pos: (usize, usize),
+        product: Option<ParticleKind>) -> Result<(), SandError> {
+    let Some(kind) = product else {
+        return system.delete_particle(pos.0, pos.1);
+    };
+    system.convert_particle(pos.0, pos.1, kind)?;
+    let i = system.grid.ind(pos.0, pos.1);
+    system.grid.temperatures[i] = system.initial_temperature(kind);
+    Ok(())
+}
+
+/// Diffuse heat between edge-adjacent cells. Empty cells and void boundaries
+/// act as air held at the ambient temperature; walls are insulating. All
+/// flows are computed from the temperatures at the start of the pass.
+/// Particles whose temperature still changes noticeably keep their
+/// surroundings awake, so heat spreads into sleeping chunks.
+fn conduct_heat(system: &mut ParticleSystem) {
+    let ambient = system.ambient_temperature;
+    let temperature_at = |system: &ParticleSystem, x: usize, y: usize| {
+        let grid = &system.grid;
+        grid.kind(x, y)
+            .map(|kind| {
+                let element = system.elements.get(kind);
+                (grid.temperatures[grid.ind(x, y)], element.conductivity)
+            })
+            .unwrap_or((ambient, AIR_CONDUCTIVITY))
+    };
+    // Updates are applied after the loop, so reads see the start of the pass
+    let cells = system.grid.awake_cells();
+    let mut updates = Vec::with_capacity(cells.len());
+    for i in cells {
+        let (x, y) = system.grid.pos(i);
+        let (temperature, conductivity) = temperature_at(system, x, y);
+        let mut heat = 0.0;
+        for offset in CARDINAL_OFFSETS {
+            let (other_temperature, other_conductivity) = match system.resolve_offset((x, y), offset) {
+                Target::Cell(nx, ny) => temperature_at(system, nx, ny),
+                Target::Void => (ambient, AIR_CONDUCTIVITY),
+                Target::Wall => continue,
+            };
+            heat += HEAT_DIFFUSION * conductance(conductivity, other_conductivity)
+                * (other_temperature - temperature);
+        }
+        let Some(kind) = system.grid.kinds[i] else {
+            continue;
+        };
+        let heat_capacity = system.elements.get(kind).heat_capacity.max(1.0);
+        updates.push((i, temperature + heat / heat_capacity));
+    }
+    for (i, temperature) in updates {
+        let change = (temperature - system.grid.temperatures[i]).abs();
+        system.grid.temperatures[i] = temperature;
+        if change > SETTLED_HEAT {
+            let (x, y) = system.grid.pos(i);
+            system.wake(x, y);
+        }
+    }
+}
+
+/// Convert particles whose temperature crossed one of their element's phase
+/// transition thresholds. Particles keep their id, cell, velocity and
+/// temperature.
+fn transition_phases(system: &mut ParticleSystem) -> Result<(), SandError> {
+    let grid = &system.grid;
+    let conversions: Vec<(usize, ParticleKind)> = grid.awake_cells().into_iter()
+        .filter_map(|i| {
+            let kind = grid.kinds[i]?;
+            system.elements.get(kind).transitions.iter()
+                .find(|transition| transition.threshold.is_crossed(grid.temperatures[i]))
+                .map(|transition| (i, transition.product))
+        })
+        .collect();
+    for (i, kind) in conversions {
+        let (x, y) = system.grid.pos(i);
+        system.convert_particle(x, y, kind)?;
+    }
+    Ok(())
+}
+
+/// Ignite flammable particles that touch a flame or are hot enough, then
+/// advance every fire by one tick. Burning particles heat up, release flames
+/// and smoke into empty neighbours, and turn into their burn product once
+/// burnt out. Fires, and exposed particles yet to catch, keep their chunk
+/// awake, so only awake cells need visiting.
+fn combust(system: &mut ParticleSystem) -> Result<(), SandError> {
+    let mut ignitions = vec![];
+    for i in system.grid.awake_cells() {
+        let Some(kind) = system.grid.kinds[i] else {
+            continue;
+        };
+        let element = system.elements.get(kind);
+        if system.grid.burning[i].is_some() || element.flammability <= 0.0 {
+            continue;
+        }
+        let exposed = system.grid.temperatures[i] >= element.ignition_temperature
+            || touches_flame(system, system.grid.pos(i));
+        if !exposed {
+            continue;
+        }
+        if system.rng.gen::<f64>() < element.flammability {
+            let (lo, hi) = element.burn_duration;
+            ignitions.push((i, system.grid.ids[i], system.rng.gen_range(lo..=hi)));
+        } else {
+            // Keep rolling next tick, as if the chunk never slept
+            let (x, y) = system.grid.pos(i);
+            system.wake(x, y);
+        }
+    }
+    for (i, id, ticks) in ignitions {
+        // An earlier blast may have replaced the particle
+        let Some(kind) = system.grid.kinds[i].filter(|_| system.grid.ids[i] == id) else {
+            continue;
+        };
+        let element = system.elements.get(kind);
+        if element.blast_radius > 0.0 {
+            let (pos, radius, strength) = (system.grid.pos(i), element.blast_radius, element.blast_strength);
+            system.delete_particle(pos.0, pos.1)?;
+            explode(system, pos, radius, strength)?;
+        } else {
+            system.grid.burning[i] = Some(ticks);
+        }
+    }
+
+    let burning: Vec<usize> = system.grid.awake_cells().into_iter()
+        .filter(|&i| system.grid.burning[i].is_some())
+        .collect();
+    for i in burning {
+        let Some(kind) = system.grid.kinds[i] else {
+            continue;
+        };
+        let grid = &mut system.grid;
+        grid.temperatures[i] = grid.temperatures[i].max(FLAME_TEMPERATURE);
+        let pos = grid.pos(i);
+        grid.wake(pos.0, pos.1, system.boundaries);
+        match grid.burning[i] {
+            Some(0) => {
+                let product = system.elements.get(kind).burn_product;
+                apply_product(system, pos, product)?;
+                continue;
+            }
+            Some(ticks) => grid.burning[i] = Some(ticks - 1),
+            None => continue,
+        }
+        let (flame, smoke) = (system.elements.flame, system.elements.smoke);
+        for (emission, chance) in [(flame, FLAME_EMISSION), (smoke, SMOKE_EMISSION)] {
+            if let Some(kind) = emission.filter(|_| system.rng.gen::<f64>() < chance) {
+                let offset = *NEIGHBOUR_OFFSETS.choose(&mut system.rng).expect("Empty offsets");
+                if let Target::Cell(x, y) = system.resolve_offset(pos, offset) {
+                    if system.grid.kind(x, y).is_none() {
+                        system.create_particle(x, y, kind)?;
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Count down the lifetime of transient particles, turning those that run out
+/// into their element's decay product. Transient particles keep their chunk
+/// awake until they are gone, so only awake cells are counted down.
+fn decay(system: &mut ParticleSystem) -> Result<(), SandError> {
+    let mut expired = vec![];
+    for i in system.grid.awake_cells() {
+        let grid = &mut system.grid;
+        match grid.lifetimes[i] {
+            Some(0) => expired.push((i, grid.kinds[i])),
+            Some(ticks) => {
+                grid.lifetimes[i] = Some(ticks - 1);
+                let (x, y) = grid.pos(i);
+                grid.wake(x, y, system.boundaries);
+            }
+            None => {}
+        }
+    }
+    for (i, kind) in expired {
+        let Some(kind) = kind else {
+            continue;
+        };
+        let product = system.elements.get(kind).decay_product;
+        apply_product(system, system.grid.pos(i), product)?;
+    }
+    Ok(())
+}
+
+/// Blast everything within `radius` of `center`. The force falls off linearly
+/// from `strength` at the centre; particles whose hardness it exceeds are
+/// destroyed, while the rest are heated and movable ones flung outwards.
+/// Explosives caught in the blast detonate in turn, in the same tick, so
+/// packed charges go off together.
+fn explode(
+        system: &mut ParticleSystem, center: (usize, usize), radius: f64,
+        strength: f64) -> Result<(), SandError> {
+    let mut blasts = VecDeque::from([(center, radius, strength)]);
+    while let Some((center, radius, strength)) = blasts.pop_front() {
+        let center = Coord::new(center.0 as f64, center.1 as f64);
+        for (x, y) in system.circle_points(center, radius) {
+            let (dx, dy) = (x as f64 - center.x, y as f64 - center.y);
+            let dist = (dx*dx + dy*dy).sqrt();
+            let falloff = 1.0 - dist / radius;
+            let force = strength * falloff;
+            let Some(kind) = system.grid.kind(x, y) else {
+                if let Some(flame) = system.elements.flame.filter(|_| system.rng.gen::<f64>() < FIREBALL * falloff) {
+                    system.create_particle(x, y, flame)?;
+                }
+                continue;
+            };
+            let element = system.elements.get(kind);
+            if element.blast_radius > 0.0 {
+                blasts.push_back(((x, y), element.blast_radius, element.blast_strength));
+                system.delete_particle(x, y)?;
+                continue;
+            }
+            if force > element.hardness {
+                system.delete_particle(x, y)?;
+                continue;
+            }
+            let grid = &mut system.grid;
+            let i = grid.ind(x, y);
+            grid.temperatures[i] = grid.temperatures[i].max(FLAME_TEMPERATURE * falloff);
+            grid.wake(x, y, system.boundaries);
+            if element.is_movable() && dist > 0.0 {
+                grid.velocities[i].0 += BLAST_IMPULSE * force * dx / dist;
+                grid.velocities[i].1 += BLAST_IMPULSE * force * dy / dist;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether any neighbour of `pos` is burning or is a flame.
+fn touches_flame(system: &ParticleSystem, pos: (usize, usize)) -> bool {
+    let grid = &system.grid;
+    NEIGHBOUR_OFFSETS.iter().any(|&offset| {
+        let Target::Cell(x, y) = system.resolve_offset(pos, offset) else {
+            return false;
+        };
+        grid.kind(x, y).is_some_and(|kind| {
+            grid.burning[grid.ind(x, y)].is_some() || Some(kind) == system.elements.flame
+        })
+    })
+}
+
+/// Effective conductivity between two materials in contact, limited by the
+/// worse conductor.
+fn conductance(a: f64, b: f64) -> f64 {
+    if a + b <= 0.0 {
+        0.0
+    } else {
+        2.0 * a * b / (a + b)
+    }
+}
+
+/// Outcome of trying to move a particle by one cell.
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+enum Move {
+    To((usize, usize)),
+    Blocked,
+    /// The particle left through a void boundary or a drain and was removed.
+    Removed,
+}
+
+/// Run the movement rules over every awake chunk.
+///
+/// Chunks are visited in four checkerboard phases, so chunks within a phase
+/// are at least a chunk apart. No particle reaches further than half a chunk
+/// per tick, so chunks in the same phase never touch the same cells and, with
+/// the `parallel` feature, are swept on separate threads. Each chunk draws
+/// from its own random stream, so the outcome depends only on the seed and
+/// not on how chunks are scheduled.
+///
+/// Within a chunk, rows are swept starting from the side gravity pulls
+/// towards, so that particles falling into already-visited cells are not
+/// moved twice in the same tick. Along rows with no horizontal pull, the sweep
+/// direction is picked at random per row to avoid piles drifting to one side.
+/// Other moves can still land a particle in a cell not yet visited, so
+/// destination cells are flagged as updated and skipped for the rest of the
+/// tick.
+fn move_particles(system: &mut ParticleSystem) {
+    let seed: u64 = system.rng.gen();
+    let (width, height) = (system.grid.width, system.grid.height);
+    let (chunks_x, chunks_y) = system.grid.chunk_counts();
+    let mut phases: [Vec<usize>; 4] = Default::default();
+    for cy in 0..chunks_y {
+        for cx in 0..chunks_x {
+            if system.grid.is_chunk_awake(cx, cy) {
+                phases[(cy % 2) * 2 + cx % 2].push(cy * chunks_x + cx);
+            }
+        }
+    }
+
+    let ParticleSystem { grid, elements, boundaries, gravity, forces, fixtures, sweep_pool, .. } = system;
+    let Grid { kinds, velocities, temperatures, lifetimes, burning, ids, .. } = grid;
+    let mut updated = vec![false; width * height];
+    let world = World {
+        kinds: Shared::new(kinds),
+        velocities: Shared::new(velocities),
+        temperatures: Shared::new(temperatures),
+        lifetimes: Shared::new(lifetimes),
+        burning: Shared::new(burning),
+        ids: Shared::new(ids),
+        updated: Shared::new(&mut updated),
+        elements,
+        boundaries: *boundaries,
+        gravity: *gravity,
+        forces,
+        fixtures,
+        width,
+        height,
+        chunks_x,
+    };
+    let mut touched = vec![];
+    for chunks in &phases {
+        for outcome in sweep_phase(sweep_pool, &world, chunks, seed) {
+            touched.extend(outcome.touched);
+        }
+    }
+
+    for (x, y) in touched {
+        grid.wake(x, y, *boundaries);
+    }
+}
+
+/// Stand-in for the worker pool of the `parallel` feature; every chunk is
+/// swept on the calling thread.
+#[cfg(any(not(feature = "parallel"), target_arch = "wasm32"))]
+#[derive(Default)]
+pub(crate) struct SweepPool {}
+
+/// Sweep the given chunks of one phase one after another.
+#[cfg(any(not(feature = "parallel"), target_arch = "wasm32"))]
+fn sweep_phase(_pool: &mut SweepPool, world: &World, chunks: &[usize], seed: u64) -> Vec<Outcome> {
+    chunks.iter().map(|&chunk| sweep_chunk(world, chunk, seed)).collect()
+}
+
+/// Work handed to a pool thread.
+#[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// Threads the movement sweep is spread over, kept for the life of a
+/// `ParticleSystem` so that ticks do not pay for spawning them. Workers are
+/// started by the first sweep that needs them.
+#[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+pub(crate) struct SweepPool {
+    /// Threads sweeping a phase, the calling one included.
+    threads: usize,
+    workers: Vec<mpsc::Sender<Job>>,
+    handles: Vec<JoinHandle<()>>,
+}
+#[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+impl SweepPool {
+    fn new(threads: usize) -> Self {
+        Self { threads: threads.max(1), workers: vec![], handles: vec![] }
+    }
+
+    /// Run the jobs at the same time, the first on the calling thread and the
+    /// rest on workers, and return their results in order. A panic in any job
+    /// is resumed once all of them have finished.
+    fn scope<'a, T: Send + 'a>(&mut self, jobs: Vec<Box<dyn FnOnce() -> T + Send + 'a>>) -> Vec<T> {
+        let mut jobs = jobs.into_iter();
+        let Some(first) = jobs.next() else {
+            return vec![];
+        };
+        let (done, results) = mpsc::channel();
+        let mut n_sent = 0;
+        for (k, job) in jobs.enumerate() {
+            let done = done.clone();
+            let job: Box<dyn FnOnce() + Send + 'a> = Box::new(move || {
+                let _ = done.send((k, panic::catch_unwind(AssertUnwindSafe(job))));
+            });
+            // Safety: the job only outlives `'a` in the type. It reports on
+            // `done` when it finishes, and we wait below until every job has
+            // either reported or been dropped unrun, before returning.
+            let job: Job = unsafe { std::mem::transmute::<Box<dyn FnOnce() + Send + 'a>, Job>(job) };
+            if let Err(mpsc::SendError(job)) = self.worker(k).send(job) {
+                job();
+            }
+            n_sent += 1;
+        }
+        drop(done);
+        let own = panic::catch_unwind(AssertUnwindSafe(first));
+        let mut rest: Vec<Option<thread::Result<T>>> = (0..n_sent).map(|_| None).collect();
+        for _ in 0..n_sent {
+            let (k, result) = results.recv().expect("Sweep job dropped unrun");
+            rest[k] = Some(result);
+        }
+        std::iter::once(own).chain(rest.into_iter().flatten())
+            .map(|result| result.unwrap_or_else(|payload| panic::resume_unwind(payload)))
+            .collect()
+    }
+
+    /// Queue of the `k`th worker, starting the workers if need be.
+    fn worker(&mut self, k: usize) -> &mpsc::Sender<Job> {
+        while self.workers.len() <= k {
+            let (jobs, queue) = mpsc::channel::<Job>();
+            self.handles.push(thread::spawn(move || {
+                for job in queue {
+                    job();
+                }
+            }));
+            self.workers.push(jobs);
+        }
+        &self.workers[k]
+    }
+}
+#[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+impl Default for SweepPool {
+    fn default() -> Self {
+        Self::new(thread::available_parallelism().map_or(1, |n| n.get()))
+    }
+}
+#[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+impl Drop for SweepPool {
+    fn drop(&mut self) {
+        // Closing the queues ends the workers' loops
+        self.workers.clear();
+        for handle in self.handles.drain(..) {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+impl ParticleSystem {
+    /// Spread the movement step over `threads` threads, the calling one
+    /// included. Defaults to the number of available cores. Results do not
+    /// depend on it.
+    pub fn set_sweep_threads(&mut self, threads: usize) {
+        self.sweep_pool = SweepPool::new(threads);
+    }
+    pub fn sweep_threads(&self) -> usize {
+        self.sweep_pool.threads
+    }
+}
+
+/// Sweep the given chunks of one phase, spread over the pool's threads.
+/// Outcomes come back in chunk order, the same as when sweeping sequentially.
+#[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+fn sweep_phase(pool: &mut SweepPool, world: &World, chunks: &[usize], seed: u64) -> Vec<Outcome> {
+    // Across a wrapping edge, the first and last chunks along an axis are only
+    // a chunk apart if the axis spans an even number of whole chunks
+    let spaced = |len: usize, low: BoundaryMode, high: BoundaryMode| {
+        (low != BoundaryMode::Wrap && high != BoundaryMode::Wrap) || len.is_multiple_of(2 * CHUNK_SIZE)
+    };
+    let b = world.boundaries;
+    if pool.threads == 1 || chunks.len() < 2
+            || !spaced(world.width, b.left, b.right) || !spaced(world.height, b.bottom, b.top) {
+        return chunks.iter().map(|&chunk| sweep_chunk(world, chunk, seed)).collect();
+    }
+    let jobs = chunks.chunks(chunks.len().div_ceil(pool.threads))
+        .map(|group| Box::new(move || {
+            group.iter().map(|&chunk| sweep_chunk(world, chunk, seed)).collect::<Vec<_>>()
+        }) as Box<dyn FnOnce() -> Vec<Outcome> + Send + '_>)
+        .collect();
+    pool.scope(jobs).into_iter().flatten().collect()
+}
+
+/// Run the movement rules over the cells of one chunk.
+fn sweep_chunk(world: &World, chunk: usize, seed: u64) -> Outcome {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    rng.set_stream(chunk as u64);
+    let (cx, cy) = (chunk % world.chunks_x, chunk / world.chunks_x);
+    let xs = cx * CHUNK_SIZE..((cx + 1) * CHUNK_SIZE).min(world.width);
+    let ys = cy * CHUNK_SIZE..((cy + 1) * CHUNK_SIZE).min(world.height);
+    let mut sweep = Sweep { world, rng, touched: vec![], xs: xs.clone(), ys: ys.clone() };
+    let (gx, gy) = world.gravity;
+    let rows: Vec<usize> = if gy <= 0.0 { ys.collect() } else { ys.rev().collect() };
+    for y in rows {
+        let left_to_right = if gx == 0.0 { sweep.rng.gen() } else { gx < 0.0 };
+        let row: Vec<usize> = if left_to_right { xs.clone().collect() } else { xs.clone().rev().collect() };
+        for x in row {
+            if sweep.is_updated(x, y) {
+                continue;
+            }
+            if let Some(kind) = sweep.get(x, y) {
+                if let Some((nx, ny)) = step_particle(&mut sweep, kind, (x, y)) {
+                    sweep.mark_updated(nx, ny);
+                }
+            }
+        }
+    }
+    Outcome { touched: sweep.touched }
+}
+
+/// Apply the movement rule for the particle's element. Returns the particle's
+/// position after the move, or `None` if it left the world.
+fn step_particle(sweep: &mut Sweep, kind: ParticleKind, pos: (usize, usize)) -> Option<(usize, usize)> {
+    let world = sweep.world;
+    let element = world.elements.get(kind);
+    if !element.is_movable() {
+        return Some(pos);
+    }
+    let state = element.state;
+    let force = world.forces.get(pos.0, pos.1);
+    let accel = (
+        world.gravity.0 * element.grav_scale + force.0,
+        world.gravity.1 * element.grav_scale + force.1);
+    let fall = nearest_direction(accel);
+    let new_pos = step_ballistic(sweep, pos, accel, fall)?;
+    // Gases keep wandering even while drifting, so plumes spread out
+    if state == ElementState::Gas {
+        return step_gas(sweep, new_pos, fall);
+    }
+    if new_pos != pos {
+        return Some(new_pos);
+    }
+    let Some(fall) = fall else {
+        return Some(pos);
+    };
+    match state {
+        ElementState::Powder => step_powder(sweep, pos, fall),
+        ElementState::Liquid => step_liquid(sweep, pos, fall, element.dispersion),
+        ElementState::Solid | ElementState::Gas => Some(pos),
+    }
+}
+/// Index into `DIRECTIONS` of the neighbour closest in angle to `v`, or `None`
+/// for the zero vector.
+fn nearest_direction(v: (f64, f64)) -> Option<usize> {
+    if v == (0.0, 0.0) {
+        return None;
+    }
+    let eighths = (v.1.atan2(v.0) / FRAC_PI_4).round() as i64;
+    Some(eighths.rem_euclid(8) as usize)
+}
+
+/// Neighbour offset `turns` eighths of a turn counter-clockwise from
+/// `DIRECTIONS[dir]`.
+fn rotate(dir: usize, turns: i64) -> (i64, i64) {
+    DIRECTIONS[(dir as i64 + turns).rem_euclid(8) as usize]
+}
+
+fn dot(a: (f64, f64), b: (f64, f64)) -> f64 {
+    a.0 * b.0 + a.1 * b.1
+}
+
+fn normalized(v: (f64, f64)) -> (f64, f64) {
+    let norm = dot(v, v).sqrt();
+    (v.0 / norm, v.1 / norm)
+}
+
+/// Accelerate the particle by `accel` and trace it along its velocity, cell
+/// by cell, until it has covered this tick's distance or hits something.
+/// Lighter particles on the path are displaced, which ends the trace early.
+/// Hitting something while moving along `fall` counts as landing: the speed
+/// along the fall direction is lost and partly converted to sideways speed.
+fn step_ballistic(
+        sweep: &mut Sweep, pos: (usize, usize),
+        accel: (f64, f64), fall: Option<usize>) -> Option<(usize, usize)> {
+    let world = sweep.world;
+    let (mut vx, mut vy) = *sweep.velocity(pos);
+    vx = (vx + accel.0).clamp(-MAX_SPEED, MAX_SPEED);
+    vy = (vy + accel.1).clamp(-MAX_SPEED, MAX_SPEED);
+    if sweep.get(pos.0, pos.1).is_some_and(|kind| world.elements.get(kind).state == ElementState::Gas) {
+        vx *= GAS_DRAG;
+        vy *= GAS_DRAG;
+    }
+    let fall_unit = fall.map(|_| normalized(accel));
+
+    // Trace in unit steps along the dominant axis so consecutive cells on the
+    // path are always neighbours. The path is computed in unwrapped
+    // coordinates and each step resolved against the boundaries.
+    let n = vx.abs().max(vy.abs()).ceil() as usize;
+    let mut cur = pos;
+    let mut prev = (pos.0 as i64, pos.1 as i64);
+    for k in 1..=n {
+        let t = k as f64 / n as f64;
+        let target = (
+            (pos.0 as f64 + vx * t).round() as i64,
+            (pos.1 as f64 + vy * t).round() as i64);
+        let offset = (target.0 - prev.0, target.1 - prev.1);
+        if offset == (0, 0) {
+            continue;
+        }
+        let occupant = match sweep.resolve_offset(cur, offset) {
+            Target::Cell(x, y) => sweep.get(x, y).map(|kind| (kind, (x, y))),
+            _ => None,
+        };
+        match sweep.try_displace(cur, offset) {
+            Move::To(next) => {
+                cur = next;
+                prev = target;
+                if occupant.is_some() {
+                    vx *= DISPLACE_DRAG;
+                    vy *= DISPLACE_DRAG;
+                    break;
+                }
+            }
+            Move::Removed => return None,
+            Move::Blocked => {
+                if let Some((kind, other)) = occupant {
+                    if world.elements.get(kind).is_movable() {
+                        let velocity = sweep.velocity(other);
+                        velocity.0 += IMPACT_TRANSFER * vx;
+                        velocity.1 += IMPACT_TRANSFER * vy;
+                    }
+                }
+                let offset_f = (offset.0 as f64, offset.1 as f64);
+                match fall_unit.filter(|&g| dot(offset_f, g) > 0.0) {
+                    Some(g) => {
+                        let along = dot((vx, vy), g);
+                        let side = (-g.1, g.0);
+                        let sideways = dot((vx, vy), side);
+                        let dir = if sideways != 0.0 {
+                            sideways.signum()
+                        } else if sweep.rng.gen() { 1.0 } else { -1.0 };
+                        vx += dir * SPLASH * along.abs() * side.0 - along * g.0;
+                        vy += dir * SPLASH * along.abs() * side.1 - along * g.1;
+                    }
+                    None => {
+                        let normal = normalized(offset_f);
+                        let into = dot((vx, vy), normal);
+                        vx -= (1.0 + RESTITUTION) * into * normal.0;
+                        vy -= (1.0 + RESTITUTION) * into * normal.1;
+                    }
+                }
+                break;
+            }
+        }
+    }
+
+    let resting = match (fall, fall_unit) {
+        (Some(fall), Some(g)) => {
+            let resting = match sweep.resolve_offset(cur, rotate(fall, 0)) {
+                Target::Cell(x, y) => sweep.get(x, y).is_some(),
+                Target::Wall => true,
+                Target::Void => false,
+            };
+            if resting {
+                let along = dot((vx, vy), g);
+                vx = along * g.0 + GROUND_FRICTION * (vx - along * g.0);
+                vy = along * g.1 + GROUND_FRICTION * (vy - along * g.1);
+            }
+            resting
+        }
+        _ => false,
+    };
+    // Slow drifters may take several ticks to cross a cell, so keep them awake
+    if !resting && vx.abs().max(vy.abs()) > SETTLED_SPEED {
+        sweep.wake(cur.0, cur.1);
+    }
+    *sweep.velocity(cur) = (vx, vy);
+    Some(cur)
+}
+
+/// Powders that cannot fall slide diagonally down, relative to the fall
+/// direction. Lighter particles in the way are displaced upwards.
+fn step_powder(
+        sweep: &mut Sweep, pos: (usize, usize),
+        fall: usize) -> Option<(usize, usize)> {
+    let turn = if sweep.rng.gen() { 1 } else { -1 };
+    for offset in [rotate(fall, turn), rotate(fall, -turn)] {
+        match sweep.try_displace(pos, offset) {
+            Move::To(next) => return Some(next),
+            Move::Removed => return None,
+            Move::Blocked => {}
+        }
+    }
+    Some(pos)
+}
+
+/// Liquids slide like powders, but when they cannot fall they spread sideways
+/// by up to `dispersion` cells, stopping early at an obstacle or a drop.
+fn step_liquid(
+        sweep: &mut Sweep, pos: (usize, usize),
+        fall: usize, dispersion: usize) -> Option<(usize, usize)> {
+    let new_pos = step_powder(sweep, pos, fall)?;
+    if new_pos != pos {
+        return Some(new_pos);
+    }
+    let turn = if sweep.rng.gen() { 2 } else { -2 };
+    let spread = spread_liquid(sweep, pos, fall, rotate(fall, turn), dispersion)?;
+    if spread != pos {
+        return Some(spread);
+    }
+    spread_liquid(sweep, pos, fall, rotate(fall, -turn), dispersion)
+}
+
+/// Gases wander to a random empty neighbour, favouring the direction they
+/// drift in. Without any drift they wander in all directions.
+fn step_gas(
+        sweep: &mut Sweep, pos: (usize, usize),
+        fall: Option<usize>) -> Option<(usize, usize)> {
+    let mut offsets = match fall {
+        Some(fall) => vec![
+            rotate(fall, 1), rotate(fall, -1), rotate(fall, 2), rotate(fall, -2),
+            rotate(fall, 0)],
+        None => DIRECTIONS.to_vec(),
+    };
+    offsets.shuffle(&mut sweep.rng);
+    for offset in offsets {
+        match sweep.try_move(pos, offset) {
+            Move::To(next) => return Some(next),
+            Move::Removed => return None,
+            Move::Blocked => {}
+        }
+    }
+    Some(pos)
+}
+
+fn spread_liquid(
+        sweep: &mut Sweep, pos: (usize, usize),
+        fall: usize, offset: (i64, i64), dispersion: usize) -> Option<(usize, usize)> {
+    let mut cur = pos;
+    for _ in 0..dispersion.min(MAX_SPREAD) {
+        match sweep.try_move(cur, offset) {
+            Move::To(next) => cur = next,
+            Move::Removed => return None,
+            Move::Blocked => break,
+        }
+        let drop = match sweep.resolve_offset(cur, rotate(fall, 0)) {
+            Target::Cell(x, y) => sweep.get(x, y).is_none(),
+            Target::Wall => false,
+            Target::Void => true,
+        };
+        if drop {
+            break;
+        }
+    }
+    Some(cur)
+}
+
+/// Slice that sweep workers read and write concurrently.
+struct Shared<'a, T> {
+    ptr: *mut T,
+    len: usize,
+    _marker: PhantomData<&'a mut [T]>,
+}
+// Safety: workers only ever access disjoint elements, see `Sweep`.
+unsafe impl<T: Send> Sync for Shared<'_, T> {}
+impl<'a, T> Shared<'a, T> {
+    fn new(slice: &'a mut [T]) -> Self {
+        Self { ptr: slice.as_mut_ptr(), len: slice.len(), _marker: PhantomData }
+    }
+    /// # Safety
+    /// No other thread may access element `i` while the result is alive.
+    #[allow(clippy::mut_from_ref)]
+    unsafe fn get_mut(&self, i: usize) -> &mut T {
+        assert!(i < self.len, "Index out of bounds");
+        &mut *self.ptr.add(i)
+    }
+}
+
+/// State shared by all chunks during the movement sweep: the grid's per-cell
+/// arrays plus everything the movement rules read.
+struct World<'a> {
+    kinds: Shared<'a, Option<ParticleKind>>,
+    velocities: Shared<'a, (f64, f64)>,
+    temperatures: Shared<'a, f64>,
+    lifetimes: Shared<'a, Option<u32>>,
+    burning: Shared<'a, Option<u32>>,
+    ids: Shared<'a, ParticleId>,
+    updated: Shared<'a, bool>,
+    elements: &'a Elements,
+    boundaries: Boundaries,
+    gravity: (f64, f64),
+    forces: &'a ForceField,
+    fixtures: &'a BTreeMap<(usize, usize), Fixture>,
+    width: usize,
+    height: usize,
+    chunks_x: usize,
+}
+
+/// Changes made by sweeping one chunk, applied once all chunks are done.
+struct Outcome {
+    /// Cells written to or needing a wake-up.
+    touched: Vec<(usize, usize)>,
+}
+
+/// Distance from `v` to the nearest coordinate in `span`, along an axis of
+/// length `len` that may wrap around.
+fn reach(span: &Range<usize>, v: usize, len: usize) -> usize {
+    if span.contains(&v) {
+        return 0;
+    }
+    let around = |a: usize, b: usize| a.abs_diff(b).min(len - a.abs_diff(b));
+    around(v, span.start).min(around(v, span.end - 1))
+}
+
+/// Sweep of a single chunk.
+///
+/// Every access through a `Sweep` stays within `MAX_SPEED + 2` cells of the
+/// chunk, which is less than half a chunk, as debug builds check. Chunks
+/// swept concurrently are a whole chunk apart, so no cell or flag is ever
+/// accessed by two threads at once.
+struct Sweep<'w, 'a> {
+    world: &'w World<'a>,
+    rng: ChaCha8Rng,
+    touched: Vec<(usize, usize)>,
+    /// Cells of the chunk being swept.
+    xs: Range<usize>,
+    ys: Range<usize>,
+}
+impl Sweep<'_, '_> {
+    /// Index of a cell in the per-cell arrays. Every access goes through
+    /// here, so debug builds check it stays within reach of the chunk.
+    fn ind(&self, pos: (usize, usize)) -> usize {
+        debug_assert!(
+            reach(&self.xs, pos.0, self.world.width) <= CHUNK_SIZE / 2
+                && reach(&self.ys, pos.1, self.world.height) <= CHUNK_SIZE / 2,
+            "Cell {:?} out of reach of chunk {:?}x{:?}", pos, self.xs, self.ys);
+        pos.1 * self.world.width + pos.0
+    }
+    fn get(&self, x: usize, y: usize) -> Option<ParticleKind> {
+        if x >= self.world.width || y >= self.world.height {
+            return None;
+        }
+        // Safety: see `Sweep`
+        unsafe { *self.world.kinds.get_mut(self.ind((x, y))) }
+    }
+    fn velocity(&mut self, pos: (usize, usize)) -> &mut (f64, f64) {
+        // Safety: see `Sweep`
+        unsafe { self.world.velocities.get_mut(self.ind(pos)) }
+    }
+    fn is_updated(&self, x: usize, y: usize) -> bool {
+        // Safety: see `Sweep`
+        unsafe { *self.world.updated.get_mut(self.ind((x, y))) }
+    }
+    fn mark_updated(&mut self, x: usize, y: usize) {
+        // Safety: see `Sweep`
+        unsafe { *self.world.updated.get_mut(self.ind((x, y))) = true };
+    }
+    fn wake(&mut self, x: usize, y: usize) {
+        self.touched.push((x, y));
+    }
+    fn resolve_offset(&self, pos: (usize, usize), offset: (i64, i64)) -> Target {
+        self.world.boundaries.resolve(self.world.width, self.world.height, pos, offset)
+    }
+    /// Exchange the contents of two cells, empty or not.
+    fn swap(&mut self, a: (usize, usize), b: (usize, usize)) {
+        let (i, j) = (self.ind(a), self.ind(b));
+        if i == j {
+            return;
+        }
+        let world = self.world;
+        // Safety: see `Sweep`
+        unsafe {
+            std::mem::swap(world.kinds.get_mut(i), world.kinds.get_mut(j));
+            std::mem::swap(world.velocities.get_mut(i), world.velocities.get_mut(j));
+            std::mem::swap(world.temperatures.get_mut(i), world.temperatures.get_mut(j));
+            std::mem::swap(world.lifetimes.get_mut(i), world.lifetimes.get_mut(j));
+            std::mem::swap(world.burning.get_mut(i), world.burning.get_mut(j));
+            std::mem::swap(world.ids.get_mut(i), world.ids.get_mut(j));
+        }
+        self.touched.push(a);
+        self.touched.push(b);
+    }
+    /// Empty the cell, as `Grid::clear` does.
+    fn remove(&mut self, pos: (usize, usize)) {
+        let i = self.ind(pos);
+        let world = self.world;
+        // Safety: see `Sweep`
+        unsafe {
+            *world.kinds.get_mut(i) = None;
+            *world.velocities.get_mut(i) = (0.0, 0.0);
+            *world.temperatures.get_mut(i) = DEFAULT_AMBIENT_TEMPERATURE;
+            *world.lifetimes.get_mut(i) = None;
+            *world.burning.get_mut(i) = None;
+            *world.ids.get_mut(i) = ParticleId::NONE;
+        }
+        self.touched.push(pos);
+    }
+
+    /// Move the particle at `pos` by the given offset if the target cell is
+    /// empty. Particles moving into a void boundary or a drain are removed.
+    fn try_move(&mut self, pos: (usize, usize), offset: (i64, i64)) -> Move {
+        match self.resolve_offset(pos, offset) {
+            Target::Wall => Move::Blocked,
+            Target::Void => {
+                self.remove(pos);
+                Move::Removed
+            }
+            Target::Cell(nx, ny) => {
+                if self.get(nx, ny).is_some() {
+                    return Move::Blocked;
+                }
+                if matches!(self.world.fixtures.get(&(nx, ny)), Some(Fixture::Drain)) {
+                    self.remove(pos);
+                    return Move::Removed;
+                }
+                self.swap(pos, (nx, ny));
+                Move::To((nx, ny))
+            }
+        }
+    }
+
+    /// Like `try_move`, but if the target cell holds a strictly lighter movable
+    /// particle the two are swapped instead.
+    fn try_displace(&mut self, pos: (usize, usize), offset: (i64, i64)) -> Move {
+        let Target::Cell(nx, ny) = self.resolve_offset(pos, offset) else {
+            return self.try_move(pos, offset);
+        };
+        let Some(other) = self.get(nx, ny) else {
+            return self.try_move(pos, offset);
+        };
+        let Some(kind) = self.get(pos.0, pos.1) else {
+            return Move::Blocked;
+        };
+        let elements = self.world.elements;
+        let (a, b) = (elements.get(kind), elements.get(other));
+        if !b.is_movable() || b.density >= a.density {
+            return Move::Blocked;
+        }
+        self.swap(pos, (nx, ny));
+        Move::To((nx, ny))
+    }
 }
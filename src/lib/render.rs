@@ -19,16 +19,19 @@ pub struct Pixels {
     pub height: usize
 }
 impl Pixels {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self { data: vec![0; width * height * BYTES_PER_PIXEL], width, height }
+    }
     pub fn ind(&self, x: usize, y: usize) -> usize {
         assert!(x < self.width && y < self.height);
         BYTES_PER_PIXEL * (y * self.width + x)
     }
     /// Iterate over pixel data by location in row-fastest order.
-    pub fn iter_row_col(&self) -> ChunksExact<u8> {
+    pub fn iter_row_col(&self) -> ChunksExact<'_, u8> {
         self.data.chunks_exact(BYTES_PER_PIXEL)
     }
     /// Iterate over mutable pixel data by location in row-fastest order.
-    pub fn iter_row_col_mut(&mut self) -> ChunksExactMut<u8> {
+    pub fn iter_row_col_mut(&mut self) -> ChunksExactMut<'_, u8> {
         self.data.chunks_exact_mut(BYTES_PER_PIXEL)
     }
 }
@@ -62,11 +65,11 @@ impl Color {
         Self::new_rgba(r, g, b, 1.0)
     }
     pub fn new_rgba(r: f32, g: f32, b: f32, a: f32) -> Self {
-        assert!(0.0 <= r && r <= 1.0);
-        assert!(0.0 <= g && g <= 1.0);
-        assert!(0.0 <= b && b <= 1.0);
-        assert!(0.0 <= a && a <= 1.0);
-        Self { r: r, g: g, b: b, a: a }
+        assert!((0.0..=1.0).contains(&r));
+        assert!((0.0..=1.0).contains(&g));
+        assert!((0.0..=1.0).contains(&b));
+        assert!((0.0..=1.0).contains(&a));
+        Self { r, g, b, a }
     }
 }
 
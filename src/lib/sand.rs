@@ -1,9 +1,15 @@
+use std::collections::{BTreeMap, HashMap};
 use std::iter::zip;
-use std::slice::{Iter, IterMut};
-use slotmap::{SlotMap, DefaultKey, new_key_type};
+use std::ops::Range;
+use std::slice::Iter;
+use std::str::FromStr;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
 
+use crate::error::SandError;
 use crate::input::MouseState;
-use crate::render::{Color, Pixels, EMPTY_COLOR, fill_pix};
+use crate::physics::SweepPool;
+use crate::render::{Color, Pixels, EMPTY_COLOR, BYTES_PER_PIXEL, fill_pix};
 
 pub struct Game {
     pub running: bool,
@@ -14,6 +20,9 @@ pub struct Game {
 }
 impl Game {
     pub fn new(width: usize, height: usize) -> Self {
+        Self::with_boundaries(width, height, Boundaries::default())
+    }
+    pub fn with_boundaries(width: usize, height: usize, boundaries: Boundaries) -> Self {
         Self {
             running: false,
             last_tick: 0.0,
@@ -22,11 +31,7 @@ impl Game {
                 kind: BrushKind::Draw(ParticleKind::Base(0)),
                 radius: 4.0,
             },
-            particle_system: ParticleSystem {
-                particles: SlotMap::<ParticleInd, Particle>::with_capacity_and_key(512),
-                grid: Grid::new(width, height),
-                elements: Elements::new(),
-            },
+            particle_system: ParticleSystem::new(width, height, boundaries),
         }
     }
     pub fn draw(&self, pixels: &mut Pixels) {
@@ -35,141 +40,1099 @@ impl Game {
         let grid = &self.particle_system.grid;
         for (pix,cell) in zip(pixels.iter_row_col_mut(), grid.iter_row_col()) {
             match *cell {
-                Some(i) => {
-                    if let Some(particle) = self.particle_system.particles.get(i) {
-                        fill_pix(pix, self.particle_system.elements.get(particle.kind).color);
-                    }
+                Some(kind) => {
+                    fill_pix(pix, self.particle_system.elements.get(kind).color);
                 }
                 None => {
                     fill_pix(pix, EMPTY_COLOR);
                 }
             }
         }
+        // Force zones and fixtures show through wherever their cell is empty
+        for (pix, (cell, &accel)) in zip(
+                pixels.iter_row_col_mut(),
+                zip(grid.iter_row_col(), self.particle_system.forces.iter_row_col())) {
+            if cell.is_none() && accel != (0.0, 0.0) {
+                fill_pix(pix, FORCE_COLOR);
+            }
+        }
+        for (&(x, y), fixture) in self.particle_system.fixtures.iter() {
+            if grid.get(x, y).is_some() || x >= pixels.width || y >= pixels.height {
+                continue;
+            }
+            let color = match fixture {
+                Fixture::Emitter { .. } => EMITTER_COLOR,
+                Fixture::Drain => DRAIN_COLOR,
+            };
+            let ind = pixels.ind(x, y);
+            fill_pix(&mut pixels.data[ind..ind + BYTES_PER_PIXEL], color);
+        }
     }
 }
 
+const EMITTER_COLOR: Color = Color { r: 0.2, g: 0.6, b: 0.2, a: 1.0 };
+const DRAIN_COLOR: Color = Color { r: 0.3, g: 0.0, b: 0.3, a: 1.0 };
+const FORCE_COLOR: Color = Color { r: 0.1, g: 0.1, b: 0.25, a: 1.0 };
+
 pub struct Brush {
     pub kind: BrushKind,
     pub radius: f64,
 }
 
 pub enum BrushKind {
+    /// Removes particles, fixtures and force zones.
     Eraser,
     Draw(ParticleKind),
+    /// Places emitter fixtures spawning the given kind at the given rate.
+    Emitter(ParticleKind, f64),
+    /// Places drain fixtures.
+    Drain,
+    /// Paints a force zone, shaped relative to the brush centre.
+    Force(Force),
 }
 
-new_key_type! { pub struct ParticleInd; }
+/// Shape of the acceleration painted by a force brush, in cells/tick^2.
+#[derive(Clone,Copy,Debug)]
+pub enum Force {
+    /// The same acceleration everywhere, e.g. a fan or a conveyor.
+    Uniform(f64, f64),
+    /// Pull of the given strength towards the centre; negative pushes away.
+    Radial(f64),
+    /// Counter-clockwise swirl of the given strength about the centre;
+    /// negative swirls clockwise.
+    Vortex(f64),
+}
+impl Force {
+    /// Acceleration at `offset` from the centre of the zone.
+    pub fn accel(&self, offset: (f64, f64)) -> (f64, f64) {
+        let dist = (offset.0 * offset.0 + offset.1 * offset.1).sqrt();
+        match *self {
+            Force::Uniform(ax, ay) => (ax, ay),
+            _ if dist == 0.0 => (0.0, 0.0),
+            Force::Radial(strength) => (-strength * offset.0 / dist, -strength * offset.1 / dist),
+            Force::Vortex(strength) => (-strength * offset.1 / dist, strength * offset.0 / dist),
+        }
+    }
+}
+
+/// Persistent special behaviour attached to a cell, independent of whatever
+/// particle currently occupies it.
+#[derive(Clone,Copy,Debug)]
+pub enum Fixture {
+    /// Spawns a particle of `kind` into the cell with chance `rate` per tick
+    /// while the cell is empty.
+    Emitter { kind: ParticleKind, rate: f64 },
+    /// Deletes any particle that enters the cell.
+    Drain,
+}
+
+pub const DEFAULT_AMBIENT_TEMPERATURE: f64 = 20.0;
+/// Straight down, in cells/tick^2.
+pub const DEFAULT_GRAVITY: (f64, f64) = (0.0, -0.15);
 
 pub struct ParticleSystem {
-    // TODO: boundaries, etc?
-    pub particles: SlotMap<ParticleInd, Particle>,
     pub grid: Grid,
     pub elements: Elements,
+    pub boundaries: Boundaries,
+    /// Temperature of the air filling empty cells.
+    pub ambient_temperature: f64,
+    /// World acceleration in cells/tick^2, scaled per element by `grav_scale`.
+    /// Any direction is allowed, and zero gives a weightless world. Call
+    /// `grid.wake_all()` after changing it so settled regions react.
+    pub gravity: (f64, f64),
+    /// Emitters and drains, keyed by cell.
+    pub fixtures: BTreeMap<(usize, usize), Fixture>,
+    /// Painted force zones, applied on top of gravity.
+    pub forces: ForceField,
+    /// Source of every random choice in the simulation. ChaCha is used since
+    /// its output is the same on every platform, so a seed and the same inputs
+    /// reproduce the same world natively and in wasm.
+    pub rng: ChaCha8Rng,
+    seed: u64,
+    /// Id handed to the next particle created.
+    next_id: u64,
+    pub(crate) sweep_pool: SweepPool,
+}
+impl ParticleSystem {
+    pub fn new(width: usize, height: usize, boundaries: Boundaries) -> Self {
+        Self {
+            grid: Grid::new(width, height),
+            elements: Elements::new(),
+            boundaries,
+            ambient_temperature: DEFAULT_AMBIENT_TEMPERATURE,
+            gravity: DEFAULT_GRAVITY,
+            fixtures: BTreeMap::new(),
+            forces: ForceField::new(width, height),
+            rng: ChaCha8Rng::seed_from_u64(0),
+            seed: 0,
+            next_id: 0,
+            sweep_pool: SweepPool::default(),
+        }.with_seed(rand::random())
+    }
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.reseed(seed);
+        self
+    }
+    /// Restart the random stream from `seed`.
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng = ChaCha8Rng::seed_from_u64(seed);
+        self.seed = seed;
+    }
+    /// Seed the random stream was last started from.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+    /// Fresh id for a new particle.
+    pub fn alloc_id(&mut self) -> ParticleId {
+        self.next_id += 1;
+        ParticleId(self.next_id)
+    }
+    /// Whether `id` was handed out by `alloc_id`.
+    pub fn is_allocated(&self, id: ParticleId) -> bool {
+        id != ParticleId::NONE && id.0 <= self.next_id
+    }
+    /// Wake the chunks around a cell for the next tick; see `Grid::wake`.
+    pub fn wake(&mut self, x: usize, y: usize) {
+        self.grid.wake(x, y, self.boundaries);
+    }
+    pub fn particle_count(&self) -> usize {
+        self.grid.count()
+    }
+    pub fn is_drain(&self, x: usize, y: usize) -> bool {
+        !self.fixtures.is_empty()
+            && matches!(self.fixtures.get(&(x, y)), Some(Fixture::Drain))
+    }
+    /// Resolve the cell reached by stepping `offset` away from `pos`, applying
+    /// the boundary mode of whichever edges are crossed. Walls take precedence
+    /// over voids when leaving through a corner.
+    pub fn resolve_offset(&self, pos: (usize, usize), offset: (i64, i64)) -> Target {
+        self.boundaries.resolve(self.grid.width, self.grid.height, pos, offset)
+    }
+    /// Check the invariants of the particle storage, returning every
+    /// violation found. An empty list means the system is consistent.
+    pub fn validate(&self) -> Vec<Violation> {
+        let grid = &self.grid;
+        let n_cells = grid.width * grid.height;
+        let lengths = [
+            ("kinds", grid.kinds.len()),
+            ("velocities", grid.velocities.len()),
+            ("temperatures", grid.temperatures.len()),
+            ("lifetimes", grid.lifetimes.len()),
+            ("burning", grid.burning.len()),
+            ("ids", grid.ids.len()),
+        ];
+        let mut violations: Vec<Violation> = lengths.iter()
+            .filter(|&&(_, len)| len != n_cells)
+            .map(|&(field, len)| Violation::ArrayLength { field, len, expected: n_cells })
+            .collect();
+        if !violations.is_empty() {
+            return violations;
+        }
+        let mut seen: HashMap<ParticleId, (usize, usize)> = HashMap::new();
+        for i in 0..n_cells {
+            let pos = grid.pos(i);
+            let Some(kind) = grid.kinds[i] else {
+                let stale = [
+                    ("velocities", grid.velocities[i] != (0.0, 0.0)),
+                    ("temperatures", grid.temperatures[i] != DEFAULT_AMBIENT_TEMPERATURE),
+                    ("lifetimes", grid.lifetimes[i].is_some()),
+                    ("burning", grid.burning[i].is_some()),
+                    ("ids", grid.ids[i] != ParticleId::NONE),
+                ];
+                violations.extend(stale.iter()
+                    .filter(|&&(_, stale)| stale)
+                    .map(|&(field, _)| Violation::StaleCell { pos, field }));
+                continue;
+            };
+            if !self.elements.contains(kind) {
+                violations.push(Violation::UnknownKind { pos, kind });
+            }
+            let (vx, vy) = grid.velocities[i];
+            if !vx.is_finite() || !vy.is_finite() {
+                violations.push(Violation::NonFinite { pos, field: "velocities" });
+            }
+            if !grid.temperatures[i].is_finite() {
+                violations.push(Violation::NonFinite { pos, field: "temperatures" });
+            }
+            let id = grid.ids[i];
+            if !self.is_allocated(id) {
+                violations.push(Violation::InvalidId { pos, id });
+            } else if let Some(&first) = seen.get(&id) {
+                violations.push(Violation::DuplicateId { id, first, second: pos });
+            } else {
+                seen.insert(id, pos);
+            }
+        }
+        violations
+    }
+}
+
+/// A broken invariant of the particle storage, as reported by
+/// `ParticleSystem::validate`.
+#[derive(Clone,Debug,PartialEq)]
+pub enum Violation {
+    /// A per-cell array of the grid does not have one entry per cell. Other
+    /// checks are skipped, since cells cannot be matched up.
+    ArrayLength { field: &'static str, len: usize, expected: usize },
+    /// An occupied cell holds a kind with no entry in the element table.
+    UnknownKind { pos: (usize, usize), kind: ParticleKind },
+    /// An occupied cell has no id, or one that was never handed out.
+    InvalidId { pos: (usize, usize), id: ParticleId },
+    /// Two occupied cells share an id.
+    DuplicateId { id: ParticleId, first: (usize, usize), second: (usize, usize) },
+    /// An occupied cell has a NaN or infinite velocity or temperature.
+    NonFinite { pos: (usize, usize), field: &'static str },
+    /// An empty cell still carries particle state in the given array.
+    StaleCell { pos: (usize, usize), field: &'static str },
+}
+
+/// Wrap a coordinate along one axis of length `len`. Returns the wrapped
+/// coordinate and, if an edge was crossed, that edge's mode.
+fn resolve_axis(
+        v: i64, len: usize, low: BoundaryMode, high: BoundaryMode)
+        -> (usize, Option<BoundaryMode>) {
+    let len = len as i64;
+    if v < 0 {
+        (v.rem_euclid(len) as usize, Some(low))
+    } else if v >= len {
+        (v.rem_euclid(len) as usize, Some(high))
+    } else {
+        (v as usize, None)
+    }
+}
+
+/// Behaviour of particles crossing an edge of the world.
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum BoundaryMode {
+    /// The edge is solid and blocks movement.
+    Wall,
+    /// Particles leaving through the edge are deleted.
+    Void,
+    /// Particles leaving through the edge re-enter from the opposite one.
+    Wrap,
+}
+impl FromStr for BoundaryMode {
+    type Err = SandError;
+    /// Parse one of "wall", "void" or "wrap".
+    fn from_str(mode: &str) -> Result<Self, SandError> {
+        match mode {
+            "wall" => Ok(BoundaryMode::Wall),
+            "void" => Ok(BoundaryMode::Void),
+            "wrap" => Ok(BoundaryMode::Wrap),
+            _ => Err(SandError::UnknownBoundaryMode(mode.to_string())),
+        }
+    }
+}
+
+#[derive(Clone,Copy,Debug)]
+pub struct Boundaries {
+    pub left: BoundaryMode,
+    pub right: BoundaryMode,
+    pub bottom: BoundaryMode,
+    pub top: BoundaryMode,
+}
+impl Boundaries {
+    pub fn uniform(mode: BoundaryMode) -> Self {
+        Self { left: mode, right: mode, bottom: mode, top: mode }
+    }
+    /// Resolve the cell reached by stepping `offset` away from `pos` in a
+    /// world of the given size. See `ParticleSystem::resolve_offset`.
+    pub fn resolve(
+            &self, width: usize, height: usize, pos: (usize, usize), offset: (i64, i64))
+            -> Target {
+        let (x, x_mode) = resolve_axis(pos.0 as i64 + offset.0, width, self.left, self.right);
+        let (y, y_mode) = resolve_axis(pos.1 as i64 + offset.1, height, self.bottom, self.top);
+        match (x_mode, y_mode) {
+            (Some(BoundaryMode::Wall), _) | (_, Some(BoundaryMode::Wall)) => Target::Wall,
+            (Some(BoundaryMode::Void), _) | (_, Some(BoundaryMode::Void)) => Target::Void,
+            _ => Target::Cell(x, y),
+        }
+    }
+}
+impl Default for Boundaries {
+    fn default() -> Self {
+        Self::uniform(BoundaryMode::Wall)
+    }
+}
+
+/// Destination of a move, after boundaries have been applied.
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum Target {
+    Cell(usize, usize),
+    Wall,
+    Void,
+}
+
+/// Broad category of matter, selecting which movement rule applies.
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum ElementState {
+    Powder,
+    Liquid,
+    /// Immovable; never updated by the physics step.
+    Solid,
+    /// Drifts randomly; rises when `grav_scale` is negative.
+    Gas,
 }
 
 pub struct Element {
     name: String,
     color: Color,
-    pub grav_scale: f64
+    pub grav_scale: f64,
+    pub state: ElementState,
+    /// Max number of cells a liquid spreads sideways per tick. Movement caps
+    /// this at 8, so that chunks swept in parallel never reach each other.
+    pub dispersion: usize,
+    /// Relative density; heavier particles sink through lighter ones.
+    pub density: f64,
+    /// Range of ticks a particle survives after creation, if it is transient.
+    pub lifetime: Option<(u32, u32)>,
+    /// What a transient particle turns into once its lifetime runs out, or
+    /// `None` to vanish.
+    pub decay_product: Option<ParticleKind>,
+    /// Fraction of a temperature difference exchanged with each neighbour per
+    /// tick, in [0, 1].
+    pub conductivity: f64,
+    /// Heat needed to change temperature by one degree; at least 1.
+    pub heat_capacity: f64,
+    /// Temperature of newly created particles, or ambient if `None`.
+    pub temperature: Option<f64>,
+    /// Conversions into other elements, checked in order each tick.
+    pub transitions: Vec<PhaseTransition>,
+    /// Chance per tick of catching fire while exposed to a flame or heated past
+    /// `ignition_temperature`. Zero for non-flammable elements.
+    pub flammability: f64,
+    pub ignition_temperature: f64,
+    /// Range of ticks a particle burns for once ignited.
+    pub burn_duration: (u32, u32),
+    /// What a particle turns into once burnt out, or `None` to vanish.
+    pub burn_product: Option<ParticleKind>,
+    /// Blast force a particle withstands before being destroyed.
+    pub hardness: f64,
+    /// Radius of the blast when ignited or caught in another blast; zero for
+    /// non-explosive elements.
+    pub blast_radius: f64,
+    /// Blast force at the centre, falling off linearly to zero at the radius.
+    pub blast_strength: f64,
+}
+impl Element {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn is_movable(&self) -> bool {
+        self.state != ElementState::Solid
+    }
+}
+impl Default for Element {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            color: EMPTY_COLOR,
+            grav_scale: 1.0,
+            state: ElementState::Powder,
+            dispersion: 0,
+            density: 1.0,
+            lifetime: None,
+            decay_product: None,
+            conductivity: 0.2,
+            heat_capacity: 1.0,
+            temperature: None,
+            transitions: vec![],
+            flammability: 0.0,
+            ignition_temperature: f64::INFINITY,
+            burn_duration: (0, 0),
+            burn_product: None,
+            hardness: 1.0,
+            blast_radius: 0.0,
+            blast_strength: 0.0,
+        }
+    }
 }
 
+/// Temperature condition triggering a phase transition.
+#[derive(Clone,Copy,Debug)]
+pub enum Threshold {
+    Above(f64),
+    Below(f64),
+}
+impl Threshold {
+    pub fn is_crossed(&self, temperature: f64) -> bool {
+        match *self {
+            Threshold::Above(t) => temperature > t,
+            Threshold::Below(t) => temperature < t,
+        }
+    }
+}
+
+/// Conversion of a particle into `product` once its temperature crosses
+/// `threshold`. Hysteresis comes from giving the reverse transition on the
+/// product a threshold well apart, so particles near the boundary do not
+/// flicker between phases. Gases lose heat to the air quickly, so steam only
+/// condenses far below the boiling point, giving it time to rise.
+#[derive(Clone,Copy,Debug)]
+pub struct PhaseTransition {
+    pub threshold: Threshold,
+    pub product: ParticleKind,
+}
+
+/// Outcome of two adjacent particles reacting. Each reactant is replaced by
+/// the product in the same position, at the product's own temperature, or
+/// removed if the product is `None`.
+#[derive(Clone,Copy,Debug)]
+pub struct Reaction {
+    pub products: (Option<ParticleKind>, Option<ParticleKind>),
+    /// Chance per tick that an adjacent pair reacts.
+    pub probability: f64,
+}
+
+/// The element table. Elements can be added and tuned but not removed, so a
+/// kind that was valid once stays valid.
 pub struct Elements {
-    pub base_elements: Vec<Element>,
-    pub custom_elements: Vec<Element>,
+    base_elements: Vec<Element>,
+    custom_elements: Vec<Element>,
+    /// Flames released by burning particles; these also ignite neighbours.
+    pub flame: Option<ParticleKind>,
+    /// Smoke released by burning particles.
+    pub smoke: Option<ParticleKind>,
+    /// Reactions keyed by reactant pair. Each reaction is stored under both
+    /// orderings, with the products swapped to match.
+    pub reactions: HashMap<(ParticleKind, ParticleKind), Reaction>,
 }
 impl Elements {
     pub fn new() -> Self {
-        Self {
+        let mut elements = Self {
             base_elements: create_base_elements(),
-            custom_elements: vec![]
+            custom_elements: vec![],
+            flame: None,
+            smoke: None,
+            reactions: HashMap::new(),
+        };
+        add_base_reactions(&mut elements);
+        add_base_transitions(&mut elements);
+        add_base_combustion(&mut elements);
+        add_base_decay(&mut elements);
+        elements
+    }
+    /// Register a reaction `a + b -> c + d`, replacing any existing reaction
+    /// between `a` and `b`.
+    pub fn add_reaction(
+            &mut self, reactants: (ParticleKind, ParticleKind),
+            products: (Option<ParticleKind>, Option<ParticleKind>), probability: f64) {
+        let (a, b) = reactants;
+        let (c, d) = products;
+        self.reactions.insert((a, b), Reaction { products: (c, d), probability });
+        self.reactions.insert((b, a), Reaction { products: (d, c), probability });
+    }
+    pub fn reaction(&self, a: ParticleKind, b: ParticleKind) -> Option<&Reaction> {
+        self.reactions.get(&(a, b))
+    }
+    /// Whether `kind` has an entry in the table.
+    pub fn contains(&self, kind: ParticleKind) -> bool {
+        match kind {
+            ParticleKind::Base(i) => (i as usize) < self.base_elements.len(),
+            ParticleKind::Custom(i) => (i as usize) < self.custom_elements.len(),
         }
     }
-    pub fn get<'a>(&'a self, kind: ParticleKind) -> &'a Element {
+    /// Element of a kind, failing with `SandError::UnknownKind` if it has no
+    /// entry in the table.
+    pub fn try_get(&self, kind: ParticleKind) -> Result<&Element, SandError> {
+        self.check(kind)?;
+        Ok(self.get(kind))
+    }
+    pub fn try_get_mut(&mut self, kind: ParticleKind) -> Result<&mut Element, SandError> {
+        self.check(kind)?;
+        Ok(self.get_mut(kind))
+    }
+    /// Element of a kind known to be in the table, such as any kind in the
+    /// grid. Panics otherwise.
+    pub(crate) fn get(&self, kind: ParticleKind) -> &Element {
         match kind {
             ParticleKind::Base(i) => &self.base_elements[i as usize],
             ParticleKind::Custom(i) => &self.custom_elements[i as usize]
         }
     }
+    pub(crate) fn get_mut(&mut self, kind: ParticleKind) -> &mut Element {
+        match kind {
+            ParticleKind::Base(i) => &mut self.base_elements[i as usize],
+            ParticleKind::Custom(i) => &mut self.custom_elements[i as usize]
+        }
+    }
+    /// Add a custom element to the table, returning its kind.
+    pub fn add_custom(&mut self, element: Element) -> ParticleKind {
+        self.custom_elements.push(element);
+        ParticleKind::Custom((self.custom_elements.len() - 1) as u16)
+    }
+    /// Look up an element by name, preferring base elements.
+    pub fn find(&self, name: &str) -> Result<ParticleKind, SandError> {
+        let base = self.base_elements.iter().position(|e| e.name == name)
+            .map(|i| ParticleKind::Base(i as u16));
+        base.or_else(|| {
+            self.custom_elements.iter().position(|e| e.name == name)
+                .map(|i| ParticleKind::Custom(i as u16))
+        }).ok_or_else(|| SandError::UnknownElement(name.to_string()))
+    }
+    /// Fail with `SandError::UnknownKind` unless `kind` is in the table.
+    pub fn check(&self, kind: ParticleKind) -> Result<ParticleKind, SandError> {
+        if self.contains(kind) {
+            Ok(kind)
+        } else {
+            Err(SandError::UnknownKind(kind))
+        }
+    }
+}
+impl Default for Elements {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 fn create_base_elements() -> Vec<Element> {
-    let mut elements = Vec::<Element>::new();
-    elements.push(Element {
-        name: "Sand".to_string(),
-        color: Color::new_rgb(1.000, 0.835, 0.333),
-        grav_scale: 1.0,
-    });
-    elements.push(Element {
-        name: "Water".to_string(),
-        color: Color::new_rgb(0.000, 0.000, 1.000),
-        grav_scale: 1.0,
-    });
-    elements
+    vec![
+        Element {
+            name: "Sand".to_string(),
+            color: Color::new_rgb(1.000, 0.835, 0.333),
+            grav_scale: 1.0,
+            state: ElementState::Powder,
+            density: 1.6,
+            conductivity: 0.3,
+            heat_capacity: 1.0,
+            hardness: 2.0,
+            ..Default::default()
+        },
+        Element {
+            name: "Water".to_string(),
+            color: Color::new_rgb(0.000, 0.000, 1.000),
+            grav_scale: 1.0,
+            state: ElementState::Liquid,
+            dispersion: 4,
+            density: 1.0,
+            conductivity: 0.5,
+            heat_capacity: 4.0,
+            hardness: 0.5,
+            ..Default::default()
+        },
+        Element {
+            name: "Oil".to_string(),
+            color: Color::new_rgb(0.300, 0.200, 0.100),
+            grav_scale: 1.0,
+            state: ElementState::Liquid,
+            dispersion: 3,
+            density: 0.8,
+            conductivity: 0.15,
+            heat_capacity: 2.0,
+            flammability: 0.2,
+            ignition_temperature: 250.0,
+            burn_duration: (30, 60),
+            hardness: 0.5,
+            ..Default::default()
+        },
+        Element {
+            name: "Wall".to_string(),
+            color: Color::new_rgb(0.500, 0.500, 0.500),
+            state: ElementState::Solid,
+            density: 10.0,
+            conductivity: 0.05,
+            heat_capacity: 10.0,
+            hardness: f64::INFINITY,
+            ..Default::default()
+        },
+        Element {
+            name: "Stone".to_string(),
+            color: Color::new_rgb(0.350, 0.330, 0.310),
+            state: ElementState::Solid,
+            density: 2.5,
+            conductivity: 0.4,
+            heat_capacity: 1.0,
+            hardness: 12.0,
+            ..Default::default()
+        },
+        Element {
+            name: "Steam".to_string(),
+            color: Color::new_rgb(0.800, 0.850, 0.900),
+            grav_scale: -0.5,
+            state: ElementState::Gas,
+            density: 0.01,
+            conductivity: 0.1,
+            heat_capacity: 2.0,
+            temperature: Some(110.0),
+            hardness: 0.0,
+            ..Default::default()
+        },
+        Element {
+            name: "Smoke".to_string(),
+            color: Color::new_rgb(0.250, 0.250, 0.250),
+            grav_scale: -0.3,
+            state: ElementState::Gas,
+            density: 0.02,
+            lifetime: Some((120, 240)),
+            conductivity: 0.1,
+            heat_capacity: 1.0,
+            temperature: Some(60.0),
+            hardness: 0.0,
+            ..Default::default()
+        },
+        Element {
+            name: "Lava".to_string(),
+            color: Color::new_rgb(1.000, 0.300, 0.000),
+            state: ElementState::Liquid,
+            dispersion: 1,
+            density: 2.8,
+            conductivity: 0.4,
+            heat_capacity: 1.5,
+            temperature: Some(1200.0),
+            hardness: 3.0,
+            ..Default::default()
+        },
+        Element {
+            name: "Ice".to_string(),
+            color: Color::new_rgb(0.700, 0.900, 1.000),
+            state: ElementState::Solid,
+            density: 0.9,
+            conductivity: 0.5,
+            heat_capacity: 2.0,
+            temperature: Some(-10.0),
+            hardness: 4.0,
+            ..Default::default()
+        },
+        Element {
+            name: "Glass".to_string(),
+            color: Color::new_rgba(0.750, 0.900, 0.900, 0.600),
+            state: ElementState::Solid,
+            density: 2.5,
+            conductivity: 0.2,
+            heat_capacity: 1.0,
+            hardness: 3.0,
+            ..Default::default()
+        },
+        Element {
+            name: "Fire".to_string(),
+            color: Color::new_rgb(1.000, 0.600, 0.100),
+            grav_scale: -1.0,
+            state: ElementState::Gas,
+            density: 0.005,
+            lifetime: Some((15, 40)),
+            conductivity: 0.3,
+            heat_capacity: 1.0,
+            temperature: Some(900.0),
+            hardness: 0.0,
+            ..Default::default()
+        },
+        Element {
+            name: "Wood".to_string(),
+            color: Color::new_rgb(0.450, 0.280, 0.120),
+            state: ElementState::Solid,
+            density: 0.7,
+            conductivity: 0.1,
+            heat_capacity: 2.0,
+            flammability: 0.03,
+            ignition_temperature: 350.0,
+            burn_duration: (150, 300),
+            hardness: 5.0,
+            ..Default::default()
+        },
+        Element {
+            name: "Ash".to_string(),
+            color: Color::new_rgb(0.600, 0.600, 0.580),
+            state: ElementState::Powder,
+            density: 0.6,
+            conductivity: 0.1,
+            heat_capacity: 1.0,
+            hardness: 0.5,
+            ..Default::default()
+        },
+        Element {
+            name: "Gunpowder".to_string(),
+            color: Color::new_rgb(0.200, 0.200, 0.220),
+            state: ElementState::Powder,
+            density: 1.7,
+            flammability: 0.9,
+            ignition_temperature: 200.0,
+            hardness: 1.0,
+            blast_radius: 4.0,
+            blast_strength: 6.0,
+            ..Default::default()
+        },
+        Element {
+            name: "TNT".to_string(),
+            color: Color::new_rgb(0.800, 0.100, 0.100),
+            state: ElementState::Solid,
+            density: 1.6,
+            flammability: 0.6,
+            ignition_temperature: 250.0,
+            hardness: 2.0,
+            blast_radius: 10.0,
+            blast_strength: 15.0,
+            ..Default::default()
+        },
+        Element {
+            name: "Ember".to_string(),
+            color: Color::new_rgb(1.000, 0.450, 0.150),
+            state: ElementState::Powder,
+            density: 0.8,
+            lifetime: Some((60, 120)),
+            conductivity: 0.3,
+            heat_capacity: 1.0,
+            temperature: Some(600.0),
+            hardness: 0.5,
+            ..Default::default()
+        },
+    ]
+}
+
+fn add_base_reactions(elements: &mut Elements) {
+    let base = |name: &str| elements.find(name).expect("Missing base element");
+    let (water, lava, stone, steam) = (base("Water"), base("Lava"), base("Stone"), base("Steam"));
+    elements.add_reaction((water, lava), (Some(steam), Some(stone)), 0.5);
+}
+
+fn add_base_transitions(elements: &mut Elements) {
+    let base = |name: &str| elements.find(name).expect("Missing base element");
+    let (sand, water, stone, steam, lava, ice, glass) = (
+        base("Sand"), base("Water"), base("Stone"), base("Steam"), base("Lava"),
+        base("Ice"), base("Glass"));
+    let transitions = [
+        (water, Threshold::Above(100.0), steam),
+        (steam, Threshold::Below(50.0), water),
+        (water, Threshold::Below(0.0), ice),
+        (ice, Threshold::Above(2.0), water),
+        (lava, Threshold::Below(700.0), stone),
+        (stone, Threshold::Above(1000.0), lava),
+        (sand, Threshold::Above(900.0), glass),
+    ];
+    for (kind, threshold, product) in transitions {
+        elements.get_mut(kind).transitions.push(PhaseTransition { threshold, product });
+    }
+}
+
+fn add_base_combustion(elements: &mut Elements) {
+    let base = |name: &str| elements.find(name).expect("Missing base element");
+    let (wood, ash, fire, smoke) = (base("Wood"), base("Ash"), base("Fire"), base("Smoke"));
+    elements.flame = Some(fire);
+    elements.smoke = Some(smoke);
+    elements.get_mut(wood).burn_product = Some(ash);
+}
+
+fn add_base_decay(elements: &mut Elements) {
+    let base = |name: &str| elements.find(name).expect("Missing base element");
+    let (ember, ash) = (base("Ember"), base("Ash"));
+    elements.get_mut(ember).decay_product = Some(ash);
 }
 
+/// Side length of the square chunks the grid is partitioned into for sleeping.
+pub const CHUNK_SIZE: usize = 32;
+
+/// The grid owns all particle state, one array per field indexed by cell, so
+/// passes over a single field stay cache-friendly and moving a particle is a
+/// swap of its cell with the destination. The arrays can be read from
+/// outside the crate but only written through `ParticleSystem`, which checks
+/// every particle going in.
+///
+/// The grid is partitioned into chunks of `CHUNK_SIZE` cells, each awake or
+/// asleep. The physics step only visits awake chunks. Anything that changes a
+/// cell wakes the chunks around it for the next tick, and a chunk where
+/// nothing happened during a tick falls asleep.
 pub struct Grid {
-    pub width: usize,
-    pub height: usize,
-    pub cells: Vec<Option<ParticleInd>>,
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+    /// Per-cell particle data in row-major order. A cell is empty when its
+    /// kind is `None`, in which case its other fields hold their defaults.
+    pub(crate) kinds: Vec<Option<ParticleKind>>,
+    pub(crate) velocities: Vec<(f64, f64)>,
+    pub(crate) temperatures: Vec<f64>,
+    /// Remaining ticks before a transient particle disappears.
+    pub(crate) lifetimes: Vec<Option<u32>>,
+    /// Remaining ticks of burning, if the particle is on fire.
+    pub(crate) burning: Vec<Option<u32>>,
+    /// Identity of each particle, which moves with it between cells.
+    pub(crate) ids: Vec<ParticleId>,
+    chunks_x: usize,
+    /// Chunks visited during the current tick.
+    awake: Vec<bool>,
+    /// Chunks to visit during the next tick.
+    woken: Vec<bool>,
 }
 impl Grid {
     pub fn new(width: usize, height: usize) -> Self {
+        let chunks_x = width.div_ceil(CHUNK_SIZE);
+        let n_chunks = chunks_x * height.div_ceil(CHUNK_SIZE);
+        let n_cells = width * height;
         Grid {
-            width: width,
-            height: height,
-            cells: vec![None; width * height],
+            width,
+            height,
+            kinds: vec![None; n_cells],
+            velocities: vec![(0.0, 0.0); n_cells],
+            temperatures: vec![DEFAULT_AMBIENT_TEMPERATURE; n_cells],
+            lifetimes: vec![None; n_cells],
+            burning: vec![None; n_cells],
+            ids: vec![ParticleId::NONE; n_cells],
+            chunks_x,
+            awake: vec![false; n_chunks],
+            woken: vec![true; n_chunks],
+        }
+    }
+    pub fn width(&self) -> usize {
+        self.width
+    }
+    pub fn height(&self) -> usize {
+        self.height
+    }
+    /// Kind of every cell, indexed like `ind`.
+    pub fn kinds(&self) -> &[Option<ParticleKind>] {
+        &self.kinds
+    }
+    pub fn velocities(&self) -> &[(f64, f64)] {
+        &self.velocities
+    }
+    pub fn temperatures(&self) -> &[f64] {
+        &self.temperatures
+    }
+    pub fn lifetimes(&self) -> &[Option<u32>] {
+        &self.lifetimes
+    }
+    pub fn burning(&self) -> &[Option<u32>] {
+        &self.burning
+    }
+    pub fn ids(&self) -> &[ParticleId] {
+        &self.ids
+    }
+    fn chunk_ind(&self, x: usize, y: usize) -> usize {
+        (y / CHUNK_SIZE) * self.chunks_x + x / CHUNK_SIZE
+    }
+    /// Wake the chunks containing the cell and its neighbours for the next
+    /// tick. Neighbours across an edge are found through `boundaries`, so a
+    /// change next to a wrapping edge also wakes the far side.
+    pub fn wake(&mut self, x: usize, y: usize, boundaries: Boundaries) {
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                if let Target::Cell(nx, ny) = boundaries.resolve(self.width, self.height, (x, y), (dx, dy)) {
+                    let c = self.chunk_ind(nx, ny);
+                    self.woken[c] = true;
+                }
+            }
+        }
+    }
+    /// Wake every chunk, e.g. after a change to world-wide settings.
+    pub fn wake_all(&mut self) {
+        self.woken.fill(true);
+    }
+    /// Number of chunks across and up the grid.
+    pub fn chunk_counts(&self) -> (usize, usize) {
+        (self.chunks_x, self.awake.len() / self.chunks_x.max(1))
+    }
+    pub fn is_chunk_awake(&self, cx: usize, cy: usize) -> bool {
+        cx < self.chunks_x && self.awake.get(cy * self.chunks_x + cx).copied().unwrap_or(false)
+    }
+    /// Whether the cell's chunk is visited during the current tick.
+    pub fn is_awake(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height && self.awake[self.chunk_ind(x, y)]
+    }
+    /// Start a new tick: chunks woken since the last call become awake and
+    /// all others fall asleep.
+    pub fn advance_chunks(&mut self) {
+        std::mem::swap(&mut self.awake, &mut self.woken);
+        self.woken.fill(false);
+    }
+    /// Ranges of x covered by awake chunks in row `y`, left to right, with
+    /// adjacent chunks merged.
+    pub fn awake_spans(&self, y: usize) -> Vec<Range<usize>> {
+        let mut spans: Vec<Range<usize>> = vec![];
+        if y >= self.height {
+            return spans;
+        }
+        let row = (y / CHUNK_SIZE) * self.chunks_x;
+        for cx in 0..self.chunks_x {
+            if !self.awake[row + cx] {
+                continue;
+            }
+            let (x0, x1) = (cx * CHUNK_SIZE, ((cx + 1) * CHUNK_SIZE).min(self.width));
+            match spans.last_mut() {
+                Some(span) if span.end == x0 => span.end = x1,
+                _ => spans.push(x0..x1),
+            }
         }
+        spans
     }
-    pub fn get(&self, x: usize, y: usize) -> Option<ParticleInd> {
+    /// Number of chunks visited during the current tick.
+    pub fn awake_chunks(&self) -> usize {
+        self.awake.iter().filter(|&&awake| awake).count()
+    }
+    /// Kind of the particle in the cell, or `None` if empty or out of bounds.
+    pub fn kind(&self, x: usize, y: usize) -> Option<ParticleKind> {
         if x >= self.width || y >= self.height {
             None
         } else {
-            self.cells[self.ind(x, y)]
+            self.kinds[self.ind(x, y)]
         }
     }
-    pub fn set(&mut self, x: usize, y: usize, val: Option<ParticleInd>) -> UpdateResult {
+    /// Copy of the particle in the cell, if any.
+    pub fn get(&self, x: usize, y: usize) -> Option<Particle> {
+        let kind = self.kind(x, y)?;
+        let i = self.ind(x, y);
+        Some(Particle {
+            kind,
+            velocity: self.velocities[i],
+            lifetime: self.lifetimes[i],
+            temperature: self.temperatures[i],
+            burning: self.burning[i],
+            id: self.ids[i],
+        })
+    }
+    /// Write a cell without checking the particle's kind or id, and without
+    /// waking it; see `ParticleSystem::set_particle` for the checked version.
+    pub(crate) fn set(&mut self, x: usize, y: usize, val: Option<Particle>) -> Result<(), SandError> {
         if x >= self.width || y >= self.height {
-            UpdateResult::Err("Out of bounds")
+            return Err(SandError::OutOfBounds { x, y });
         }
-        else {
-            let i = self.ind(x, y);
-            self.cells[i] = val;
-            UpdateResult::Ok
+        let i = self.ind(x, y);
+        match val {
+            Some(particle) => {
+                self.kinds[i] = Some(particle.kind);
+                self.velocities[i] = particle.velocity;
+                self.lifetimes[i] = particle.lifetime;
+                self.temperatures[i] = particle.temperature;
+                self.burning[i] = particle.burning;
+                self.ids[i] = particle.id;
+            }
+            None => self.clear(i),
         }
+        Ok(())
+    }
+    /// Empty the cell at index `i`, resetting it to the state of a new grid,
+    /// without waking it.
+    pub(crate) fn clear(&mut self, i: usize) {
+        self.kinds[i] = None;
+        self.velocities[i] = (0.0, 0.0);
+        self.temperatures[i] = DEFAULT_AMBIENT_TEMPERATURE;
+        self.lifetimes[i] = None;
+        self.burning[i] = None;
+        self.ids[i] = ParticleId::NONE;
     }
-    fn ind(&self, x: usize, y: usize) -> usize {
+    /// Index of the cell in the per-cell arrays.
+    pub fn ind(&self, x: usize, y: usize) -> usize {
         y * self.width + x
     }
-    pub fn iter_row_col(&self) -> Iter<Option<ParticleInd>> {
-        self.cells.iter()
+    /// Cell coordinates of an index into the per-cell arrays.
+    pub fn pos(&self, i: usize) -> (usize, usize) {
+        (i % self.width, i / self.width)
+    }
+    /// Indices of the occupied cells in awake chunks, row by row.
+    pub fn awake_cells(&self) -> Vec<usize> {
+        (0..self.height)
+            .flat_map(|y| self.awake_spans(y).into_iter().map(move |span| (y, span)))
+            .flat_map(|(y, span)| span.map(move |x| y * self.width + x))
+            .filter(|&i| self.kinds[i].is_some())
+            .collect()
+    }
+    /// Number of particles in the grid.
+    pub fn count(&self) -> usize {
+        self.kinds.iter().filter(|kind| kind.is_some()).count()
     }
-    pub fn iter_row_col_mut(&mut self) -> IterMut<Option<ParticleInd>> {
-        self.cells.iter_mut()
+    pub fn iter_row_col(&self) -> Iter<'_, Option<ParticleKind>> {
+        self.kinds.iter()
     }
 }
-pub enum UpdateResult {
-    Ok,
-    Err(&'static str)
+/// Per-cell acceleration in cells/tick^2 felt by movable particles in that
+/// cell, independent of their element.
+pub struct ForceField {
+    pub width: usize,
+    pub height: usize,
+    pub cells: Vec<(f64, f64)>,
+}
+impl ForceField {
+    pub fn new(width: usize, height: usize) -> Self {
+        ForceField {
+            width,
+            height,
+            cells: vec![(0.0, 0.0); width * height],
+        }
+    }
+    pub fn get(&self, x: usize, y: usize) -> (f64, f64) {
+        if x >= self.width || y >= self.height {
+            (0.0, 0.0)
+        } else {
+            self.cells[y * self.width + x]
+        }
+    }
+    pub fn set(&mut self, x: usize, y: usize, accel: (f64, f64)) -> Result<(), SandError> {
+        if x >= self.width || y >= self.height {
+            Err(SandError::OutOfBounds { x, y })
+        }
+        else {
+            self.cells[y * self.width + x] = accel;
+            Ok(())
+        }
+    }
+    pub fn iter_row_col(&self) -> Iter<'_, (f64, f64)> {
+        self.cells.iter()
+    }
 }
 
-#[derive(Clone,Copy,Debug)]
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Hash)]
 pub enum ParticleKind {
     Base(u16),
     Custom(u16)
 }
 
-#[derive(Debug)]
+/// Stable identity of a particle, kept as it moves between cells.
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Hash,PartialOrd,Ord)]
+pub struct ParticleId(pub u64);
+impl ParticleId {
+    /// Id held by empty cells; real particles are numbered from 1.
+    pub const NONE: ParticleId = ParticleId(0);
+}
+
+/// Everything stored about one particle, as copied out of or into a `Grid`
+/// cell.
+#[derive(Clone,Copy,Debug)]
 pub struct Particle {
     pub kind: ParticleKind,
-    pub position: (usize, usize),
     pub velocity: (f64, f64),
+    /// Remaining ticks before a transient particle disappears.
+    pub lifetime: Option<u32>,
+    pub temperature: f64,
+    /// Remaining ticks of burning, if the particle is on fire.
+    pub burning: Option<u32>,
+    pub id: ParticleId,
 }
 impl Default for Particle {
     fn default() -> Self {
         Self {
             kind: ParticleKind::Base(0),
-            position: Default::default(),
             velocity: Default::default(),
+            lifetime: None,
+            temperature: DEFAULT_AMBIENT_TEMPERATURE,
+            burning: None,
+            id: ParticleId::NONE,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // States only reachable from inside the crate, since the grid's arrays
+    // cannot be written from outside it.
+
+    #[test]
+    fn validate_reports_stale_empty_cell() {
+        let mut system = ParticleSystem::new(8, 8, Boundaries::default());
+        let i = system.grid.ind(5, 5);
+        system.grid.velocities[i] = (1.0, 0.0);
+        system.grid.lifetimes[i] = Some(3);
+        assert_eq!(system.validate(), vec![
+            Violation::StaleCell { pos: (5, 5), field: "velocities" },
+            Violation::StaleCell { pos: (5, 5), field: "lifetimes" },
+        ]);
+    }
+
+    #[test]
+    fn validate_reports_unknown_kind() {
+        let mut system = ParticleSystem::new(8, 8, Boundaries::default());
+        let i = system.grid.ind(2, 0);
+        system.grid.kinds[i] = Some(ParticleKind::Custom(7));
+        system.grid.ids[i] = system.alloc_id();
+        assert_eq!(system.validate(), vec![
+            Violation::UnknownKind { pos: (2, 0), kind: ParticleKind::Custom(7) },
+        ]);
+    }
+}
@@ -6,6 +6,6 @@ pub struct Coord {
 
 impl Coord {
     pub fn new(x: f64, y: f64) -> Self {
-        Self { x: x, y: y }
+        Self { x, y }
     }
 }
@@ -1,7 +1,9 @@
 use wasm_bindgen::prelude::*;
 
+use crate::error::SandError;
 use crate::input;
 use crate::physics;
+use crate::render;
 use crate::sand;
 use crate::util;
 use crate::webgl;
@@ -10,10 +12,31 @@ use crate::webgl;
 pub struct WasmGameContext {
     game: sand::Game,
     renderer: Option<webgl::Renderer>,
+    /// Frame drawn for a renderer on another thread; see `draw_pixels`.
+    frame: render::Pixels,
     step_time_ms: f64,
+    /// Wall time not yet consumed by ticks, carried between frames.
+    accumulator_ms: f64,
+    /// Most ticks run in a single update; any backlog beyond this is dropped.
+    max_ticks_per_update: usize,
+    dropped_ticks: usize,
+    /// Simulated time per unit of wall time.
+    time_scale: f64,
+    /// Frozen for inspection; unlike stopping, single steps still work.
+    paused: bool,
 }
 
 const STEP_TIME_MS_60FPS: f64 = 1000.0 / 60.0;
+const DEFAULT_MAX_TICKS_PER_UPDATE: usize = 4;
+const MIN_TIME_SCALE: f64 = 0.25;
+const MAX_TIME_SCALE: f64 = 8.0;
+
+/// Errors reach JavaScript as exceptions carrying the error's message.
+impl From<SandError> for JsValue {
+    fn from(err: SandError) -> Self {
+        JsError::new(&err.to_string()).into()
+    }
+}
 
 #[wasm_bindgen]
 impl WasmGameContext {
@@ -21,8 +44,97 @@ impl WasmGameContext {
         Self {
             game: sand::Game::new(width, height),
             renderer: None,
+            frame: render::Pixels::new(width, height),
             step_time_ms: STEP_TIME_MS_60FPS,
+            accumulator_ms: 0.0,
+            max_ticks_per_update: DEFAULT_MAX_TICKS_PER_UPDATE,
+            dropped_ticks: 0,
+            time_scale: 1.0,
+            paused: false,
+        }
+    }
+    /// Create a game whose edges all use the given boundary mode, one of
+    /// "wall", "void" or "wrap".
+    pub fn new_with_boundary(width: usize, height: usize, mode: &str) -> Result<WasmGameContext, JsValue> {
+        let mut context = Self::new(width, height);
+        context.game.particle_system.boundaries = sand::Boundaries::uniform(mode.parse()?);
+        Ok(context)
+    }
+    /// Change the boundary mode of one edge ("left", "right", "bottom" or
+    /// "top") of a running game.
+    pub fn set_boundary(&mut self, edge: &str, mode: &str) -> Result<(), JsValue> {
+        let mode: sand::BoundaryMode = mode.parse()?;
+        let boundaries = &mut self.game.particle_system.boundaries;
+        match edge {
+            "left" => boundaries.left = mode,
+            "right" => boundaries.right = mode,
+            "bottom" => boundaries.bottom = mode,
+            "top" => boundaries.top = mode,
+            _ => return Err(SandError::UnknownEdge(edge.to_string()).into()),
+        }
+        self.game.particle_system.grid.wake_all();
+        Ok(())
+    }
+    /// Set the temperature of the air filling empty cells. NaN or infinite
+    /// temperatures are ignored.
+    pub fn set_ambient_temperature(&mut self, temperature: f64) {
+        if !temperature.is_finite() {
+            return;
+        }
+        self.game.particle_system.ambient_temperature = temperature;
+        self.game.particle_system.grid.wake_all();
+    }
+    /// Restart the simulation's random stream from `seed`, so the same inputs
+    /// replay identically.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.game.particle_system.reseed(seed);
+    }
+    pub fn seed(&self) -> u64 {
+        self.game.particle_system.seed()
+    }
+    /// Set the world gravity vector in cells/tick^2, with y pointing up. NaN
+    /// or infinite components are ignored, leaving gravity unchanged.
+    pub fn set_gravity(&mut self, x: f64, y: f64) {
+        if !x.is_finite() || !y.is_finite() {
+            return;
         }
+        self.game.particle_system.gravity = (x, y);
+        self.game.particle_system.grid.wake_all();
+    }
+    /// Select the element drawn by the brush, by name.
+    pub fn set_brush_element(&mut self, name: &str) -> Result<(), JsValue> {
+        let kind = self.game.particle_system.elements.find(name)?;
+        self.game.brush.kind = sand::BrushKind::Draw(kind);
+        Ok(())
+    }
+    /// Make the brush place emitters spawning the named element, with chance
+    /// `rate` per tick per cell.
+    pub fn set_brush_emitter(&mut self, name: &str, rate: f64) -> Result<(), JsValue> {
+        let kind = self.game.particle_system.elements.find(name)?;
+        self.game.brush.kind = sand::BrushKind::Emitter(kind, rate);
+        Ok(())
+    }
+    pub fn set_brush_drain(&mut self) {
+        self.game.brush.kind = sand::BrushKind::Drain;
+    }
+    /// Make the brush paint a uniform force, e.g. wind, in cells/tick^2. Like
+    /// the other force brushes, NaN or infinite input leaves the brush as it
+    /// was.
+    pub fn set_brush_wind(&mut self, x: f64, y: f64) {
+        self.set_brush_force(sand::Force::Uniform(x, y), &[x, y]);
+    }
+    /// Make the brush paint a pull towards the brush centre; negative
+    /// `strength` pushes away instead.
+    pub fn set_brush_attractor(&mut self, strength: f64) {
+        self.set_brush_force(sand::Force::Radial(strength), &[strength]);
+    }
+    /// Make the brush paint a counter-clockwise swirl about the brush centre;
+    /// negative `strength` swirls clockwise.
+    pub fn set_brush_vortex(&mut self, strength: f64) {
+        self.set_brush_force(sand::Force::Vortex(strength), &[strength]);
+    }
+    pub fn set_brush_eraser(&mut self) {
+        self.game.brush.kind = sand::BrushKind::Eraser;
     }
     pub fn bind_canvas(&mut self, canvas: web_sys::HtmlCanvasElement)
         -> Result<(), JsValue> {
@@ -32,6 +144,52 @@ impl WasmGameContext {
     pub fn set_running(&mut self, running: bool, timestamp: f64) {
         self.game.running = running;
         self.game.last_tick = timestamp;
+        self.accumulator_ms = 0.0;
+    }
+    /// Limit how many ticks a single update may run to catch up, so a slow
+    /// frame cannot snowball into ever slower ones.
+    pub fn set_tick_budget(&mut self, max_ticks: usize) {
+        self.max_ticks_per_update = max_ticks;
+    }
+    /// Number of grid chunks simulated on the latest tick, out of the total
+    /// the world is split into; idle regions sleep and are skipped.
+    pub fn awake_chunks(&self) -> usize {
+        self.game.particle_system.grid.awake_chunks()
+    }
+    /// Total ticks skipped so far because an update was over budget.
+    pub fn dropped_ticks(&self) -> usize {
+        self.dropped_ticks
+    }
+    /// Run the simulation faster or slower than real time, clamped to
+    /// 0.25x-8x. NaN or infinite scales are ignored. Returns the scale actually
+    /// applied.
+    pub fn set_time_scale(&mut self, scale: f64) -> f64 {
+        if scale.is_finite() {
+            self.time_scale = scale.clamp(MIN_TIME_SCALE, MAX_TIME_SCALE);
+        }
+        self.time_scale
+    }
+    pub fn time_scale(&self) -> f64 {
+        self.time_scale
+    }
+    /// Freeze the simulation without stopping it; time spent paused is not
+    /// caught up on resume.
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+    /// Advance exactly `n` ticks now, regardless of wall time, pausing or
+    /// whether the game is running.
+    pub fn step_once(&mut self, n: usize) -> Result<(), JsValue> {
+        for _ in 0..n {
+            physics::step(&mut self.game.particle_system)?;
+        }
+        Ok(())
     }
     pub fn render(&mut self) -> Result<(), JsValue> {
         if let Some(renderer) = &mut self.renderer {
@@ -39,37 +197,80 @@ impl WasmGameContext {
         }
         Ok(())
     }
-    pub fn update(&mut self, timestamp: f64) {
-        let dt = timestamp - self.game.last_tick;
-        let n_ticks = (dt / self.step_time_ms).floor() as usize;
-        if n_ticks == 0 {
-            return;
-        }
-        if n_ticks > 1 {
-            // web_sys::console::log_1(&format!("Dropped {} ticks", n_ticks - 1).into());
+    /// Draw the game into a fresh RGBA buffer, for a game running in a worker
+    /// to hand to a `WasmRenderer` on the main thread.
+    pub fn draw_pixels(&mut self) -> Vec<u8> {
+        self.game.draw(&mut self.frame);
+        self.frame.data.clone()
+    }
+    /// Advance the simulation by however many fixed-length ticks fit into the
+    /// time since the last update, independent of the frame rate. Leftover
+    /// time carries over to the next update; ticks over budget are dropped.
+    /// The budget grows with the time scale, so fast-forwarding does not
+    /// count as falling behind. A failing tick is reported once its time
+    /// has been consumed, so the next update does not retry it.
+    pub fn update(&mut self, timestamp: f64) -> Result<(), JsValue> {
+        let dt = (timestamp - self.game.last_tick).max(0.0);
+        self.game.last_tick = timestamp;
+        if !self.game.running || self.paused {
+            return Ok(());
         }
-        if self.game.running {
-            physics::step(&mut self.game.particle_system);
+        self.accumulator_ms += dt * self.time_scale;
+        let n_ticks = (self.accumulator_ms / self.step_time_ms).floor() as usize;
+        let budget = (self.max_ticks_per_update as f64 * self.time_scale.max(1.0)).ceil() as usize;
+        let n_run = n_ticks.min(budget);
+        self.dropped_ticks += n_ticks - n_run;
+        self.accumulator_ms -= n_ticks as f64 * self.step_time_ms;
+        for _ in 0..n_run {
+            physics::step(&mut self.game.particle_system)?;
         }
-        self.game.last_tick = timestamp;
+        Ok(())
     }
-    pub fn mouse_down(&mut self, x: f64, y: f64) {
+    pub fn mouse_down(&mut self, x: f64, y: f64) -> Result<(), JsValue> {
         let coord = util::Coord::new(x, y);
-        input::handle_mouse_click(coord, &mut self.game);
         self.game.mouse_state = input::MouseState::Down(coord);
+        input::handle_mouse_click(coord, &mut self.game)?;
+        Ok(())
     }
-    pub fn mouse_move(&mut self, x: f64, y: f64) {
+    pub fn mouse_move(&mut self, x: f64, y: f64) -> Result<(), JsValue> {
         let coord = util::Coord::new(x, y);
         match self.game.mouse_state {
             input::MouseState::Up => {}
             input::MouseState::Down(old_coord) => {
                 web_sys::console::log_1(&format!("Drag {},{} -> {},{}", old_coord.x, old_coord.y, coord.x, coord.y).into());
-                input::handle_mouse_drag(old_coord, coord, &mut self.game);
                 self.game.mouse_state = input::MouseState::Down(coord);
+                input::handle_mouse_drag(old_coord, coord, &mut self.game)?;
             }
         }
+        Ok(())
     }
-    pub fn mouse_up(&mut self, x: f64, y: f64) {
+    pub fn mouse_up(&mut self, _x: f64, _y: f64) {
         self.game.mouse_state = input::MouseState::Up;
     }
 }
+
+impl WasmGameContext {
+    fn set_brush_force(&mut self, force: sand::Force, inputs: &[f64]) {
+        if inputs.iter().all(|v| v.is_finite()) {
+            self.game.brush.kind = sand::BrushKind::Force(force);
+        }
+    }
+}
+
+/// Shows frames drawn by a `WasmGameContext` running elsewhere, typically in a
+/// Web Worker, so the main thread only uploads pixels to WebGL.
+#[wasm_bindgen]
+pub struct WasmRenderer {
+    renderer: webgl::Renderer,
+}
+
+#[wasm_bindgen]
+impl WasmRenderer {
+    pub fn new(canvas: web_sys::HtmlCanvasElement) -> Result<WasmRenderer, JsValue> {
+        Ok(Self { renderer: webgl::Renderer::new(canvas)? })
+    }
+    /// Show a frame from `WasmGameContext::draw_pixels`.
+    pub fn render(&mut self, pixels: &[u8]) -> Result<(), JsValue> {
+        self.renderer.render_pixels(pixels)
+    }
+}
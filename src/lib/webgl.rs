@@ -1,3 +1,4 @@
+use crate::error::SandError;
 use crate::render::{Pixels, PixelFormat, PIXEL_FORMAT, BYTES_PER_PIXEL};
 use crate::sand;
 use js_sys::ArrayBuffer;
@@ -63,10 +64,10 @@ fn init_shaders(gl: &WebGL) -> Result<(WebGlShader, WebGlShader), JsError> {
         JsError::new("Failed to fetch frag shader status")
     )?;
     if !vert_shader_ok {
-        return Err(get_shader_compile_err(gl, &vert_shader, "vertex").into());
+        return Err(get_shader_compile_err(gl, &vert_shader, "vertex"));
     }
     if !frag_shader_ok {
-        return Err(get_shader_compile_err(gl, &frag_shader, "fragment").into());
+        return Err(get_shader_compile_err(gl, &frag_shader, "fragment"));
     }
     Ok((vert_shader, frag_shader))
 }
@@ -77,17 +78,17 @@ fn init_shader_program(
     let program: WebGlProgram = match gl.create_program() {
         Some(program) => { program }
         None => {
-            gl.delete_shader(Some(&vert_shader));
-            gl.delete_shader(Some(&frag_shader));
+            gl.delete_shader(Some(vert_shader));
+            gl.delete_shader(Some(frag_shader));
             return Err(JsError::new("Failed to create shader program"));
         }
     };
 
-    gl.attach_shader(&program, &vert_shader);
-    gl.attach_shader(&program, &frag_shader);
+    gl.attach_shader(&program, vert_shader);
+    gl.attach_shader(&program, frag_shader);
     gl.link_program(&program);
-    gl.delete_shader(Some(&vert_shader));
-    gl.delete_shader(Some(&frag_shader));
+    gl.delete_shader(Some(vert_shader));
+    gl.delete_shader(Some(frag_shader));
 
     let link_ok = gl.get_program_parameter(&program, WebGL::LINK_STATUS).as_bool().ok_or_else(|| {
         gl.delete_program(Some(&program));
@@ -96,7 +97,7 @@ fn init_shader_program(
     if !link_ok {
         let err = get_program_link_err(gl, &program);
         gl.delete_program(Some(&program));
-        return Err(err.into());
+        return Err(err);
     }
 
     Ok(program)
@@ -123,14 +124,14 @@ fn bind_shader_buffers(
         -> Result<(), JsValue> {
     gl.bind_buffer(WebGL::ARRAY_BUFFER, Some(&gl_data.vertex_buffer));
     let i = get_attrib_location(gl, program, "vertexPosition")?;
-    gl.vertex_attrib_pointer_with_i32(i as u32, 3, WebGL::FLOAT, false, 0, 0);
-    gl.enable_vertex_attrib_array(i as u32);
+    gl.vertex_attrib_pointer_with_i32(i, 3, WebGL::FLOAT, false, 0, 0);
+    gl.enable_vertex_attrib_array(i);
 
     gl.bind_buffer(WebGL::ELEMENT_ARRAY_BUFFER, Some(&gl_data.index_buffer));
     gl.bind_buffer(WebGL::ARRAY_BUFFER, Some(&gl_data.tex_vertex_buffer));
     let i = get_attrib_location(gl, program, "vert_texCoord")?;
-    gl.vertex_attrib_pointer_with_i32(i as u32, 2, WebGL::FLOAT, false, 0, 0);
-    gl.enable_vertex_attrib_array(i as u32);
+    gl.vertex_attrib_pointer_with_i32(i, 2, WebGL::FLOAT, false, 0, 0);
+    gl.enable_vertex_attrib_array(i);
 
     let i = get_uniform_location(gl, program, "texSampler")?;
     gl.active_texture(WebGL::TEXTURE0);
@@ -213,7 +214,7 @@ impl RendererBuffers {
             index_buffer: make_gl_buffer(&js_quad_indices.buffer(), gl, WebGL::ELEMENT_ARRAY_BUFFER)?,
             vertex_buffer: make_gl_buffer(&js_quad_vertices.buffer(), gl, WebGL::ARRAY_BUFFER)?,
             tex_vertex_buffer: make_gl_buffer(&js_quad_tex_vertices.buffer(), gl, WebGL::ARRAY_BUFFER)?,
-            texture: make_gl_texture(&pixels, gl)?,
+            texture: make_gl_texture(pixels, gl)?,
             sampler: make_gl_nearest_sampler(gl)?
         })
     }
@@ -225,9 +226,12 @@ fn make_tex_array(width: u32, height: u32) -> Vec<u8> {
 }
 
 pub struct Renderer {
+    // Canvas and GL buffers are only held to keep them alive while rendering
+    #[allow(dead_code)]
     canvas: web_sys::HtmlCanvasElement,
     gl: WebGL,
     program: WebGlProgram,
+    #[allow(dead_code)]
     gl_data: RendererBuffers,
     pixels: Pixels
 }
@@ -252,19 +256,32 @@ impl Renderer {
         bind_shader_buffers(&gl, &program, &gl_data)?;
         clear_screen(&gl);
         Ok(Self {
-            canvas: canvas,
-            gl: gl,
-            program: program,
-            gl_data: gl_data,
-            pixels: pixels
+            canvas,
+            gl,
+            program,
+            gl_data,
+            pixels
         })
     }
 
 
     pub fn render(&mut self, game: &sand::Game) -> Result<(), JsValue> {
+        game.draw(&mut self.pixels);
+        self.present()
+    }
+
+    /// Show pixel data drawn elsewhere, e.g. by a game running in a worker.
+    pub fn render_pixels(&mut self, data: &[u8]) -> Result<(), JsValue> {
+        if data.len() != self.pixels.data.len() {
+            return Err(SandError::FrameSize { expected: self.pixels.data.len(), len: data.len() }.into());
+        }
+        self.pixels.data.copy_from_slice(data);
+        self.present()
+    }
+
+    fn present(&mut self) -> Result<(), JsValue> {
         // FORNOW: do it the dumb way by clearing and re-drawing everything
         clear_screen(&self.gl);
-        game.draw(&mut self.pixels);
         upload_gl_texture(&self.pixels, &self.gl)?;
         self.gl.use_program(Some(&self.program));
         self.gl.draw_elements_with_i32(
@@ -273,5 +290,5 @@ impl Renderer {
     }
 }
 
-const VERT_SHADER_SOURCE: &'static str = include_str!("../shaders/vertex.glsl");
-const FRAG_SHADER_SOURCE: &'static str = include_str!("../shaders/fragment.glsl");
\ No newline at end of file
+const VERT_SHADER_SOURCE: &str = include_str!("../shaders/vertex.glsl");
+const FRAG_SHADER_SOURCE: &str = include_str!("../shaders/fragment.glsl");
\ No newline at end of file
@@ -0,0 +1,28 @@
+mod common;
+
+use common::{count, place, run, system_with};
+use sand_game::sand::{Boundaries, BoundaryMode};
+
+#[test]
+fn void_floor_deletes_falling_sand() {
+    let floor = Boundaries { bottom: BoundaryMode::Void, ..Boundaries::default() };
+    let mut system = system_with(4, 8, floor);
+    place(&mut system, 2, 4, "Sand");
+    run(&mut system, 20);
+    assert_eq!(system.particle_count(), 0);
+}
+
+#[test]
+fn wrapping_floor_drops_sand_back_in_at_the_top() {
+    let wrap = Boundaries { bottom: BoundaryMode::Wrap, top: BoundaryMode::Wrap, ..Boundaries::default() };
+    let mut system = system_with(4, 16, wrap);
+    place(&mut system, 2, 2, "Sand");
+    let mut heights = vec![];
+    for _ in 0..10 {
+        run(&mut system, 1);
+        let i = system.grid.kinds().iter().position(|k| k.is_some()).expect("Sand vanished");
+        heights.push(system.grid.pos(i).1);
+    }
+    assert_eq!(count(&system, "Sand"), 1);
+    assert!(heights.iter().any(|&y| y > 2), "{:?}", heights);
+}
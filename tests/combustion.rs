@@ -0,0 +1,57 @@
+mod common;
+
+use common::{count, place, run, system};
+
+#[test]
+fn lit_gunpowder_pile_goes_off_completely() {
+    let mut system = system(32, 32);
+    for x in 10..20 {
+        for y in 0..4 {
+            place(&mut system, x, y, "Gunpowder");
+        }
+    }
+    place(&mut system, 15, 4, "Fire");
+    run(&mut system, 10);
+    assert_eq!(count(&system, "Gunpowder"), 0);
+}
+
+#[test]
+fn lit_tnt_block_goes_off_completely() {
+    let mut system = system(64, 32);
+    for x in 12..52 {
+        for y in 0..8 {
+            place(&mut system, x, y, "TNT");
+        }
+    }
+    place(&mut system, 32, 8, "Fire");
+    run(&mut system, 10);
+    assert_eq!(count(&system, "TNT"), 0);
+}
+
+#[test]
+fn lit_wood_burns_down_to_ash() {
+    let mut system = system(16, 16);
+    for x in 6..10 {
+        place(&mut system, x, 4, "Wood");
+    }
+    place(&mut system, 8, 3, "Fire");
+    run(&mut system, 600);
+    assert_eq!(count(&system, "Wood"), 0);
+    assert!(count(&system, "Ash") > 0);
+}
+
+#[test]
+fn blast_destroys_sand_but_not_walls() {
+    let mut system = system(32, 32);
+    for x in 8..24 {
+        place(&mut system, x, 0, "Wall");
+    }
+    place(&mut system, 15, 1, "Sand");
+    place(&mut system, 16, 1, "TNT");
+    place(&mut system, 17, 1, "Sand");
+    place(&mut system, 16, 2, "Fire");
+    run(&mut system, 5);
+    assert_eq!(count(&system, "TNT"), 0);
+    assert_eq!(count(&system, "Sand"), 0);
+    assert_eq!(count(&system, "Wall"), 16);
+}
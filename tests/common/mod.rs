@@ -0,0 +1,57 @@
+//! Helpers shared by the integration tests. Each test binary uses only some
+//! of them.
+#![allow(dead_code)]
+
+use sand_game::physics::step;
+use sand_game::sand::{Boundaries, ParticleKind, ParticleSystem};
+
+/// Seed of every test system, so failures replay exactly.
+pub const SEED: u64 = 1;
+
+/// An empty world walled in on every side.
+pub fn system(width: usize, height: usize) -> ParticleSystem {
+    system_with(width, height, Boundaries::default())
+}
+
+/// An empty world with the given boundaries.
+pub fn system_with(width: usize, height: usize, boundaries: Boundaries) -> ParticleSystem {
+    ParticleSystem::new(width, height, boundaries).with_seed(SEED)
+}
+
+/// Kind of the element with the given name.
+pub fn kind(system: &ParticleSystem, name: &str) -> ParticleKind {
+    system.elements.find(name).expect("Missing element")
+}
+
+/// Create a particle of the named element at `x`, `y`.
+pub fn place(system: &mut ParticleSystem, x: usize, y: usize, name: &str) {
+    let kind = kind(system, name);
+    system.create_particle(x, y, kind).expect("Create failed");
+}
+
+/// Number of particles of the named element.
+pub fn count(system: &ParticleSystem, name: &str) -> usize {
+    let kind = kind(system, name);
+    system.grid.kinds().iter().filter(|&&k| k == Some(kind)).count()
+}
+
+/// Step the system `ticks` times.
+pub fn run(system: &mut ParticleSystem, ticks: usize) {
+    for _ in 0..ticks {
+        step(system).expect("Step failed");
+    }
+}
+
+/// Assert that two systems hold the same particles with bit-identical state.
+pub fn assert_identical(a: &ParticleSystem, b: &ParticleSystem) {
+    let velocities = |system: &ParticleSystem| system.grid.velocities().iter()
+        .map(|&(x, y)| (x.to_bits(), y.to_bits()))
+        .collect::<Vec<_>>();
+    let temperatures = |system: &ParticleSystem| system.grid.temperatures().iter()
+        .map(|t| t.to_bits())
+        .collect::<Vec<_>>();
+    assert_eq!(a.grid.kinds(), b.grid.kinds());
+    assert_eq!(a.grid.ids(), b.grid.ids());
+    assert_eq!(velocities(a), velocities(b));
+    assert_eq!(temperatures(a), temperatures(b));
+}
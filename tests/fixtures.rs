@@ -0,0 +1,32 @@
+mod common;
+
+use common::{count, kind, place, run, system};
+use sand_game::sand::BrushKind;
+
+#[test]
+fn emitter_keeps_spawning_while_its_cell_empties() {
+    let mut system = system(16, 16);
+    let sand = kind(&system, "Sand");
+    system.draw_point(8, 15, &BrushKind::Emitter(sand, 1.0)).expect("Draw failed");
+    run(&mut system, 30);
+    assert!(count(&system, "Sand") > 5, "{}", count(&system, "Sand"));
+}
+
+#[test]
+fn drain_swallows_falling_sand() {
+    let mut system = system(16, 16);
+    for x in 0..16 {
+        system.draw_point(x, 0, &BrushKind::Drain).expect("Draw failed");
+    }
+    place(&mut system, 8, 10, "Sand");
+    run(&mut system, 30);
+    assert_eq!(system.particle_count(), 0);
+}
+
+#[test]
+fn eraser_removes_fixtures() {
+    let mut system = system(16, 16);
+    system.draw_point(8, 0, &BrushKind::Drain).expect("Draw failed");
+    system.draw_point(8, 0, &BrushKind::Eraser).expect("Erase failed");
+    assert!(system.fixtures.is_empty());
+}
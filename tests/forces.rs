@@ -0,0 +1,61 @@
+mod common;
+
+use common::{kind, place, run, system};
+use sand_game::sand::{Brush, BrushKind, Force};
+use sand_game::util::Coord;
+
+#[test]
+fn draw_point_paints_a_single_cell() {
+    let mut system = system(16, 16);
+    let sand = kind(&system, "Sand");
+    system.draw_point(3, 4, &BrushKind::Draw(sand)).expect("Draw failed");
+    assert_eq!(system.particle_at(3, 4), Ok(sand));
+    system.draw_point(5, 5, &BrushKind::Force(Force::Uniform(0.5, 0.0))).expect("Draw failed");
+    assert_eq!(system.forces.get(5, 5), (0.5, 0.0));
+    // A lone cell is the centre of its own zone, where a vortex is still
+    system.draw_point(6, 6, &BrushKind::Force(Force::Vortex(1.0))).expect("Draw failed");
+    assert_eq!(system.forces.get(6, 6), (0.0, 0.0));
+}
+
+#[test]
+fn vortex_stroke_swirls_about_its_centre() {
+    let mut system = system(16, 16);
+    let brush = Brush { kind: BrushKind::Force(Force::Vortex(1.0)), radius: 3.0 };
+    system.fill_circle(Coord::new(8.0, 8.0), &brush).expect("Fill failed");
+    assert_eq!(system.forces.get(10, 8), (0.0, 1.0));
+    assert_eq!(system.forces.get(8, 10), (-1.0, 0.0));
+    assert_eq!(system.forces.get(6, 8), (0.0, -1.0));
+}
+
+#[test]
+fn sideways_gravity_pulls_sand_to_the_left_wall() {
+    let mut system = system(16, 16);
+    system.gravity = (-0.15, 0.0);
+    place(&mut system, 12, 8, "Sand");
+    run(&mut system, 30);
+    let sand = kind(&system, "Sand");
+    assert!((0..16).any(|y| system.grid.kind(0, y) == Some(sand)));
+}
+
+#[test]
+fn sand_floats_without_gravity() {
+    let mut system = system(16, 16);
+    system.gravity = (0.0, 0.0);
+    place(&mut system, 8, 8, "Sand");
+    run(&mut system, 30);
+    assert_eq!(system.particle_at(8, 8), Ok(kind(&system, "Sand")));
+}
+
+#[test]
+fn wind_zone_blows_sand_along_the_floor() {
+    let mut system = system(16, 16);
+    let wind = BrushKind::Force(Force::Uniform(0.3, 0.0));
+    for x in 0..16 {
+        for y in 0..4 {
+            system.draw_point(x, y, &wind).expect("Draw failed");
+        }
+    }
+    place(&mut system, 2, 0, "Sand");
+    run(&mut system, 30);
+    assert_eq!(system.particle_at(15, 0), Ok(kind(&system, "Sand")));
+}
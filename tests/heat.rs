@@ -0,0 +1,81 @@
+mod common;
+
+use common::{count, kind, place, run, system};
+use sand_game::sand::{Particle, ParticleSystem};
+
+/// Set the temperature of the particle at `x`, `y`.
+fn heat(system: &mut ParticleSystem, x: usize, y: usize, temperature: f64) {
+    let particle = system.grid.get(x, y).expect("Missing particle");
+    system.set_particle(x, y, Some(Particle { temperature, ..particle })).expect("Set failed");
+}
+
+/// Temperature of the particle at `x`, `y`.
+fn temperature(system: &ParticleSystem, x: usize, y: usize) -> f64 {
+    system.grid.get(x, y).expect("Missing particle").temperature
+}
+
+#[test]
+fn heat_flows_from_hot_to_cold() {
+    let mut system = system(16, 16);
+    place(&mut system, 7, 0, "Stone");
+    place(&mut system, 8, 0, "Stone");
+    heat(&mut system, 7, 0, 500.0);
+    run(&mut system, 5);
+    let (hot, cold) = (temperature(&system, 7, 0), temperature(&system, 8, 0));
+    assert!(hot < 500.0 && cold > 100.0 && hot > cold, "{} {}", hot, cold);
+}
+
+#[test]
+fn empty_cells_act_as_ambient_air() {
+    let mut system = system(16, 16);
+    system.ambient_temperature = 300.0;
+    place(&mut system, 8, 0, "Stone");
+    run(&mut system, 50);
+    let stone = temperature(&system, 8, 0);
+    assert!((stone - 300.0).abs() < 1.0, "{}", stone);
+}
+
+#[test]
+fn water_and_lava_stay_steam_and_stone() {
+    let mut system = system(16, 16);
+    let (water, lava, steam, stone) = (
+        kind(&system, "Water"), kind(&system, "Lava"), kind(&system, "Steam"), kind(&system, "Stone"));
+    system.elements.add_reaction((water, lava), (Some(steam), Some(stone)), 1.0);
+    place(&mut system, 7, 0, "Water");
+    place(&mut system, 8, 0, "Lava");
+    run(&mut system, 1);
+    assert_eq!((count(&system, "Steam"), count(&system, "Stone")), (1, 1));
+    assert_eq!(system.particle_count(), 2);
+}
+
+/// Ticks that steam boiled off water lasts, at least, before condensing.
+const STEAM_TICKS: usize = 50;
+
+#[test]
+fn boiled_steam_rises_before_condensing() {
+    let mut system = system(32, 64);
+    for x in 8..24 {
+        place(&mut system, x, 0, "Water");
+        heat(&mut system, x, 0, 101.0);
+    }
+    run(&mut system, 1);
+    assert_eq!(count(&system, "Steam"), 16);
+    run(&mut system, STEAM_TICKS);
+    assert_eq!(count(&system, "Steam"), 16);
+    let steam = kind(&system, "Steam");
+    let lowest = (0..system.grid.height())
+        .find(|&y| (0..system.grid.width()).any(|x| system.grid.kind(x, y) == Some(steam)));
+    assert!(lowest.is_some_and(|y| y >= 10));
+}
+
+#[test]
+fn water_freezes_in_place_in_cold_air() {
+    let mut system = system(16, 16);
+    system.ambient_temperature = -20.0;
+    place(&mut system, 8, 0, "Water");
+    let id = system.grid.get(8, 0).expect("Missing particle").id;
+    run(&mut system, 50);
+    let ice = system.grid.get(8, 0).expect("Missing particle");
+    assert_eq!(ice.kind, kind(&system, "Ice"));
+    assert_eq!(ice.id, id);
+}
@@ -0,0 +1,20 @@
+mod common;
+
+use common::{count, place, run, system};
+
+#[test]
+fn ember_decays_into_ash() {
+    let mut system = system(16, 16);
+    place(&mut system, 8, 0, "Ember");
+    let lifetime = system.grid.get(8, 0).and_then(|ember| ember.lifetime);
+    assert!(lifetime.is_some_and(|ticks| (60..=120).contains(&ticks)), "{:?}", lifetime);
+    run(&mut system, 121);
+    assert_eq!((count(&system, "Ember"), count(&system, "Ash")), (0, 1));
+}
+
+#[test]
+fn permanent_particles_have_no_lifetime() {
+    let mut system = system(16, 16);
+    place(&mut system, 8, 0, "Sand");
+    assert_eq!(system.grid.get(8, 0).and_then(|sand| sand.lifetime), None);
+}
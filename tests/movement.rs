@@ -0,0 +1,132 @@
+mod common;
+
+use common::{count, kind, place, run, system};
+use sand_game::sand::ParticleSystem;
+
+/// Height of the column of particles standing on the floor at `x`.
+fn column_height(system: &ParticleSystem, x: usize) -> usize {
+    (0..system.grid.height()).take_while(|&y| system.grid.kind(x, y).is_some()).count()
+}
+
+#[test]
+fn sand_falls_to_the_floor() {
+    let mut system = system(8, 16);
+    place(&mut system, 4, 12, "Sand");
+    run(&mut system, 30);
+    // Landing may knock it a cell or two sideways, but it ends up resting
+    let sand = kind(&system, "Sand");
+    assert!((0..8).any(|x| system.grid.kind(x, 0) == Some(sand)));
+    assert_eq!(count(&system, "Sand"), 1);
+}
+
+#[test]
+fn sand_column_topples_into_a_pile() {
+    let mut system = system(17, 16);
+    for y in 0..8 {
+        place(&mut system, 8, y, "Sand");
+    }
+    run(&mut system, 60);
+    assert_eq!(count(&system, "Sand"), 8);
+    let heights = (0..17).map(|x| column_height(&system, x)).collect::<Vec<_>>();
+    // Nothing is left floating, and the column has slid down both flanks
+    assert_eq!(heights.iter().sum::<usize>(), 8);
+    assert!(heights[8] < 8, "{:?}", heights);
+    assert!(heights[7] > 0 && heights[9] > 0, "{:?}", heights);
+}
+
+#[test]
+fn water_column_levels_out() {
+    let mut system = system(16, 16);
+    for y in 0..8 {
+        place(&mut system, 8, y, "Water");
+    }
+    run(&mut system, 100);
+    assert_eq!(count(&system, "Water"), 8);
+    let heights = (0..16).map(|x| column_height(&system, x)).collect::<Vec<_>>();
+    assert!(heights.iter().all(|&h| h <= 1), "{:?}", heights);
+}
+
+/// Fill rows `ys` of the world with the named element.
+fn fill_rows(system: &mut ParticleSystem, ys: std::ops::Range<usize>, name: &str) {
+    for y in ys {
+        for x in 0..system.grid.width() {
+            place(system, x, y, name);
+        }
+    }
+}
+
+#[test]
+fn oil_floats_up_through_water() {
+    let mut system = system(4, 8);
+    fill_rows(&mut system, 0..2, "Oil");
+    fill_rows(&mut system, 2..4, "Water");
+    run(&mut system, 100);
+    let (oil, water) = (kind(&system, "Oil"), kind(&system, "Water"));
+    for x in 0..4 {
+        assert_eq!(system.grid.kind(x, 0), Some(water));
+        assert_eq!(system.grid.kind(x, 1), Some(water));
+        assert_eq!(system.grid.kind(x, 2), Some(oil));
+        assert_eq!(system.grid.kind(x, 3), Some(oil));
+    }
+}
+
+#[test]
+fn sand_sinks_through_water() {
+    let mut system = system(4, 8);
+    fill_rows(&mut system, 0..3, "Water");
+    fill_rows(&mut system, 3..4, "Sand");
+    run(&mut system, 100);
+    // The grains settle into a slope, but all of them under the water
+    let sand = kind(&system, "Sand");
+    for x in 0..4 {
+        for y in 2..4 {
+            assert_ne!(system.grid.kind(x, y), Some(sand));
+        }
+    }
+    assert_eq!(count(&system, "Water"), 12);
+}
+
+#[test]
+fn falling_sand_accelerates() {
+    let mut system = system(4, 64);
+    place(&mut system, 2, 63, "Sand");
+    let state = |system: &ParticleSystem| {
+        let i = system.grid.kinds().iter().position(|k| k.is_some()).expect("Sand vanished");
+        (system.grid.pos(i).1, system.grid.velocities()[i].1)
+    };
+    run(&mut system, 10);
+    let (y, speed) = state(&system);
+    run(&mut system, 10);
+    let (lower, faster) = state(&system);
+    assert!(faster < speed && speed < 0.0, "{} {}", speed, faster);
+    // Falling more cells than ticks means several cells per tick
+    assert!(y - lower > 10, "{} {}", y, lower);
+}
+
+#[test]
+fn floating_wall_holds_up_sand() {
+    let mut system = system(4, 16);
+    fill_rows(&mut system, 8..9, "Wall");
+    fill_rows(&mut system, 9..10, "Sand");
+    run(&mut system, 30);
+    let (wall, sand) = (kind(&system, "Wall"), kind(&system, "Sand"));
+    for x in 0..4 {
+        assert_eq!(system.grid.kind(x, 8), Some(wall));
+        assert_eq!(system.grid.kind(x, 9), Some(sand));
+    }
+}
+
+#[test]
+fn smoke_rises_and_dissipates() {
+    let mut system = system(8, 32);
+    for x in 2..6 {
+        place(&mut system, x, 0, "Smoke");
+    }
+    run(&mut system, 40);
+    assert_eq!(count(&system, "Smoke"), 4);
+    for y in 0..16 {
+        assert!((0..8).all(|x| system.grid.kind(x, y).is_none()), "Smoke at height {}", y);
+    }
+    run(&mut system, 200);
+    assert_eq!(count(&system, "Smoke"), 0);
+}
@@ -0,0 +1,37 @@
+#![cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+
+mod common;
+
+use common::{assert_identical, place, run, system_with};
+use sand_game::sand::{Boundaries, BoundaryMode, ParticleSystem};
+
+const SIZE: usize = 128;
+const TICKS: usize = 60;
+
+/// Bands of assorted elements, stepped on `threads` sweep threads.
+fn bands(boundaries: Boundaries, threads: usize) -> ParticleSystem {
+    let mut system = system_with(SIZE, SIZE, boundaries);
+    system.set_sweep_threads(threads);
+    let names = ["Sand", "Water", "Oil", "Steam", "Wood", "Fire", "Lava", "Gunpowder"];
+    for y in SIZE / 4..SIZE {
+        for x in 0..SIZE {
+            if (x * 7 + y * 3) % 5 != 0 {
+                place(&mut system, x, y, names[(x / 16 + y / 12) % names.len()]);
+            }
+        }
+    }
+    run(&mut system, TICKS);
+    system
+}
+
+#[test]
+fn threaded_sweep_matches_sequential() {
+    let boundaries = Boundaries::default();
+    assert_identical(&bands(boundaries, 1), &bands(boundaries, 4));
+}
+
+#[test]
+fn threaded_sweep_matches_sequential_with_wrap() {
+    let boundaries = Boundaries::uniform(BoundaryMode::Wrap);
+    assert_identical(&bands(boundaries, 1), &bands(boundaries, 4));
+}
@@ -0,0 +1,27 @@
+mod common;
+
+use common::{count, kind, place, run, system};
+
+#[test]
+fn reaction_replaces_each_reactant_with_its_product() {
+    let mut system = system(16, 16);
+    let (sand, wall, stone) = (kind(&system, "Sand"), kind(&system, "Wall"), kind(&system, "Stone"));
+    system.elements.add_reaction((sand, wall), (None, Some(stone)), 1.0);
+    // Listed the other way round from the reaction
+    place(&mut system, 4, 0, "Wall");
+    place(&mut system, 5, 0, "Sand");
+    run(&mut system, 1);
+    assert_eq!(system.grid.kind(4, 0), Some(stone));
+    assert_eq!(system.particle_count(), 1);
+}
+
+#[test]
+fn only_adjacent_particles_react() {
+    let mut system = system(16, 16);
+    let (sand, wall, stone) = (kind(&system, "Sand"), kind(&system, "Wall"), kind(&system, "Stone"));
+    system.elements.add_reaction((sand, wall), (None, Some(stone)), 1.0);
+    place(&mut system, 4, 0, "Wall");
+    place(&mut system, 6, 0, "Sand");
+    run(&mut system, 10);
+    assert_eq!((count(&system, "Wall"), count(&system, "Sand")), (1, 1));
+}
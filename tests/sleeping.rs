@@ -0,0 +1,79 @@
+mod common;
+
+use common::{kind, place, system, system_with};
+use sand_game::physics::step;
+use sand_game::sand::{Boundaries, BoundaryMode, ParticleSystem};
+
+const SIZE: usize = 40;
+
+/// Step `system` for `ticks` ticks, optionally waking every chunk first so
+/// that nothing sleeps.
+fn run(mut system: ParticleSystem, ticks: usize, wake_all: bool) -> ParticleSystem {
+    for _ in 0..ticks {
+        if wake_all {
+            system.grid.wake_all();
+        }
+        step(&mut system).expect("Step failed");
+    }
+    system
+}
+
+/// Run the same scene with and without sleeping chunks, asserting that both
+/// end identically and that `changed` holds for the result.
+fn assert_sleep_invariant(
+        scene: impl Fn() -> ParticleSystem, ticks: usize,
+        changed: impl Fn(&ParticleSystem) -> bool) {
+    let sleeping = run(scene(), ticks, false);
+    let awake = run(scene(), ticks, true);
+    assert!(changed(&awake));
+    assert_eq!(sleeping.grid.kinds(), awake.grid.kinds());
+    assert_eq!(sleeping.grid.ids(), awake.grid.ids());
+}
+
+#[test]
+fn unlikely_reaction_fires_between_settled_particles() {
+    let scene = || {
+        let mut system = system(SIZE, SIZE);
+        let (sand, stone, water) = (kind(&system, "Sand"), kind(&system, "Stone"), kind(&system, "Water"));
+        system.elements.add_reaction((sand, stone), (Some(water), Some(stone)), 0.001);
+        place(&mut system, 10, 0, "Sand");
+        place(&mut system, 11, 0, "Stone");
+        system
+    };
+    let water = kind(&scene(), "Water");
+    assert_sleep_invariant(scene, 5000, |system| system.grid.kinds().contains(&Some(water)));
+}
+
+#[test]
+fn unlikely_ignition_catches_on_hot_wood() {
+    let scene = || {
+        let mut system = system(SIZE, SIZE);
+        system.ambient_temperature = 400.0;
+        let wood = kind(&system, "Wood");
+        system.elements.try_get_mut(wood).expect("Missing element").flammability = 0.002;
+        for x in 20..24 {
+            place(&mut system, x, 0, "Wood");
+        }
+        system
+    };
+    let wood = kind(&scene(), "Wood");
+    assert_sleep_invariant(scene, 3000, |system| !system.grid.kinds().contains(&Some(wood)));
+}
+
+#[test]
+fn hole_wakes_chunk_across_wrapping_edge() {
+    let scene = || {
+        let wrap = Boundaries { left: BoundaryMode::Wrap, right: BoundaryMode::Wrap, ..Boundaries::default() };
+        let mut system = system_with(64, 32, wrap);
+        for x in 0..64 {
+            place(&mut system, x, 0, "Sand");
+            place(&mut system, x, 1, "Sand");
+        }
+        place(&mut system, 63, 2, "Sand");
+        common::run(&mut system, 5);
+        // Only the sand across the edge, in the other chunk, can fill the hole
+        system.delete_particle(0, 1).expect("Delete failed");
+        system
+    };
+    assert_sleep_invariant(scene, 20, |system| system.grid.kind(0, 1).is_some());
+}
@@ -0,0 +1,85 @@
+mod common;
+
+use common::place;
+use sand_game::error::SandError;
+use sand_game::physics::step;
+use sand_game::sand::{Element, Particle, ParticleId, ParticleKind, ParticleSystem, Violation};
+
+const SIZE: usize = 16;
+
+/// A small settled system with sand along the floor.
+fn system() -> ParticleSystem {
+    let mut system = common::system(SIZE, SIZE);
+    for x in 0..4 {
+        place(&mut system, x, 0, "Sand");
+    }
+    system
+}
+
+/// Copy the particle at `from` over the one at `to`, so both share an id.
+fn duplicate(system: &mut ParticleSystem, from: (usize, usize), to: (usize, usize)) -> ParticleId {
+    let particle = system.grid.get(from.0, from.1).expect("Missing particle");
+    system.set_particle(to.0, to.1, Some(particle)).expect("Set failed");
+    particle.id
+}
+
+#[test]
+fn consistent_system_has_no_violations() {
+    let mut system = system();
+    assert_eq!(system.validate(), vec![]);
+    step(&mut system).expect("Step failed");
+    assert_eq!(system.validate(), vec![]);
+}
+
+#[test]
+fn duplicate_id() {
+    let mut system = system();
+    let id = duplicate(&mut system, (1, 0), (3, 0));
+    assert_eq!(system.validate(), vec![Violation::DuplicateId { id, first: (1, 0), second: (3, 0) }]);
+}
+
+#[test]
+fn non_finite_velocity() {
+    let mut system = system();
+    let sand = system.grid.get(2, 0).expect("Missing particle");
+    let particle = Particle { velocity: (f64::NAN, 0.0), ..sand };
+    system.set_particle(2, 0, Some(particle)).expect("Set failed");
+    assert_eq!(system.validate(), vec![Violation::NonFinite { pos: (2, 0), field: "velocities" }]);
+}
+
+#[cfg(debug_assertions)]
+#[test]
+fn debug_step_reports_violations() {
+    let mut system = system();
+    let id = duplicate(&mut system, (0, 0), (2, 0));
+    assert_eq!(
+        step(&mut system),
+        Err(SandError::Inconsistent(vec![Violation::DuplicateId { id, first: (0, 0), second: (2, 0) }])));
+}
+
+#[test]
+fn set_particle_rejects_unknown_kind_and_id() {
+    let mut system = system();
+    let sand = system.grid.get(0, 0).expect("Missing particle");
+    let unknown = Particle { kind: ParticleKind::Custom(7), ..sand };
+    assert_eq!(system.set_particle(5, 5, Some(unknown)), Err(SandError::UnknownKind(ParticleKind::Custom(7))));
+    let unnamed = Particle { id: ParticleId::NONE, ..sand };
+    assert_eq!(system.set_particle(5, 5, Some(unnamed)), Err(SandError::InvalidId(ParticleId::NONE)));
+    let moved = system.grid.get(0, 0);
+    system.set_particle(0, 0, None).expect("Clear failed");
+    system.set_particle(5, 5, moved).expect("Move failed");
+    assert_eq!(system.validate(), vec![]);
+}
+
+#[test]
+fn elements_reject_unknown_kind() {
+    let mut system = system();
+    let unknown = ParticleKind::Custom(7);
+    assert!(matches!(system.elements.try_get(unknown), Err(SandError::UnknownKind(kind)) if kind == unknown));
+    assert!(system.elements.try_get_mut(unknown).is_err());
+    let sand = system.elements.find("Sand").expect("Missing element");
+    assert_eq!(system.elements.try_get(sand).expect("Missing element").name(), "Sand");
+    let custom = system.elements.add_custom(Element::default());
+    assert_eq!(custom, ParticleKind::Custom(0));
+    assert!(system.elements.try_get(custom).is_ok());
+}
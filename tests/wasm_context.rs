@@ -0,0 +1,32 @@
+use sand_game::wasm::WasmGameContext;
+
+#[test]
+fn non_finite_gravity_is_ignored() {
+    let mut context = WasmGameContext::new(16, 16);
+    context.mouse_down(8.0, 12.0).expect("Paint failed");
+    for (x, y) in [(f64::NAN, 0.0), (0.0, f64::INFINITY)] {
+        context.set_gravity(x, y);
+        assert!(context.step_once(5).is_ok());
+    }
+}
+
+#[test]
+fn non_finite_force_brushes_are_ignored() {
+    let mut context = WasmGameContext::new(16, 16);
+    context.set_brush_element("Sand").expect("Missing element");
+    context.mouse_down(8.0, 12.0).expect("Paint failed");
+    context.set_brush_wind(f64::NAN, 0.0);
+    context.set_brush_attractor(f64::INFINITY);
+    context.set_brush_vortex(f64::NAN);
+    // Still the sand brush, so this paints nothing new over the sand
+    context.mouse_down(8.0, 12.0).expect("Paint failed");
+    assert!(context.step_once(5).is_ok());
+}
+
+#[test]
+fn non_finite_ambient_temperature_is_ignored() {
+    let mut context = WasmGameContext::new(16, 16);
+    context.mouse_down(8.0, 12.0).expect("Paint failed");
+    context.set_ambient_temperature(f64::NAN);
+    assert!(context.step_once(5).is_ok());
+}